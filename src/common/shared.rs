@@ -1,23 +1,63 @@
+use crate::control::params::{ParamId, ParamStore};
 use crate::data::presets::Preset;
+use alloc::boxed::Box;
+use alloc::sync::Arc;
 use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
 use embassy_sync::channel::Channel;
+use infinitedsp_core::core::channels::Mono;
+use infinitedsp_core::FrameProcessor;
 
 pub const SAMPLE_RATE: f32 = 48000.0;
 pub const HEAP_SIZE: usize = 400000;
 pub const BLOCK_SIZE: usize = 256;
 pub const CORE1_STACK_SIZE: usize = 4096;
+pub const MAX_VOICES: usize = 8;
 
 pub struct AudioData {
     pub buffer: [f32; BLOCK_SIZE],
 }
 
+#[derive(Clone)]
+pub struct Patch {
+    pub preset: Preset,
+    pub params: Arc<ParamStore>,
+    pub fx_params: Arc<ParamStore>,
+}
+
+impl Patch {
+    pub fn new(preset: Preset) -> Self {
+        Self {
+            preset,
+            params: Arc::new(ParamStore::new(&preset)),
+            fx_params: Arc::new(ParamStore::new(&preset)),
+        }
+    }
+}
+
+pub struct VoiceBank {
+    pub generation: u32,
+    pub processor: Box<dyn FrameProcessor<Mono> + Send>,
+    pub params: Arc<ParamStore>,
+}
+
 #[derive(Clone, Copy)]
 pub enum SystemCommand {
     ResetStorage,
+    SetParameter(ParamId, f32),
+    GetParameter(ParamId),
+    ListParameters,
+    SavePreset,
+    SetFxTails(bool),
+    Learn(Option<ParamId>),
+    SetCc(ParamId, Option<u8>),
+    SetChannel(u8),
+    SetMasterTune(f32),
 }
 
 pub static AUDIO_CHANNEL: Channel<CriticalSectionRawMutex, AudioData, 4> = Channel::new();
-pub static PRESET_CHANNEL: Channel<CriticalSectionRawMutex, Preset, 1> = Channel::new();
+pub static PRESET_CHANNEL: Channel<CriticalSectionRawMutex, Patch, 1> = Channel::new();
+pub static VOICE_BANK_CHANNEL: Channel<CriticalSectionRawMutex, VoiceBank, 1> = Channel::new();
+pub static RETIRED_CHANNEL: Channel<CriticalSectionRawMutex, VoiceBank, 2> = Channel::new();
 pub static COMMAND_CHANNEL: Channel<CriticalSectionRawMutex, SystemCommand, 2> = Channel::new();
 
 pub fn disable_denormals() {
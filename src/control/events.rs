@@ -0,0 +1,103 @@
+use embassy_time::Instant;
+use heapless::spsc::{Consumer, Producer, Queue};
+
+use crate::usb::logger::MIDI_LOG_CHANNEL;
+
+pub const EVENT_QUEUE_SIZE: usize = 128;
+
+#[derive(Clone, Copy)]
+pub enum MidiEvent {
+    NoteOn {
+        voice: u8,
+        freq: f32,
+        velocity: u8,
+        retrigger: bool,
+    },
+    NoteOff {
+        voice: u8,
+    },
+    Glide {
+        voice: u8,
+        freq: f32,
+    },
+    PitchBend(f32),
+    BendRange(f32, f32),
+    ChannelPressure(f32),
+    PolyPressure {
+        voice: u8,
+        value: f32,
+    },
+    ModWheel(f32),
+    Portamento(f32, u32),
+    Cutoff(f32),
+    Resonance(f32),
+    AllNotesOff,
+}
+
+#[derive(Clone, Copy)]
+pub struct TimedEvent {
+    pub timestamp: u64,
+    pub event: MidiEvent,
+}
+
+pub type EventQueue = Queue<TimedEvent, EVENT_QUEUE_SIZE>;
+
+pub struct EventSender {
+    producer: Producer<'static, TimedEvent>,
+}
+
+impl EventSender {
+    pub fn new(producer: Producer<'static, TimedEvent>) -> Self {
+        Self { producer }
+    }
+
+    pub fn send(&mut self, event: MidiEvent) {
+        let timed = TimedEvent {
+            timestamp: Instant::now().as_ticks(),
+            event,
+        };
+        if self.producer.enqueue(timed).is_err() {
+            let mut msg = heapless::String::<64>::new();
+            let _ = msg.push_str("\r\nEvent queue full, event dropped\r\n");
+            let _ = MIDI_LOG_CHANNEL.try_send(msg);
+        }
+    }
+}
+
+pub struct EventScheduler {
+    consumer: Consumer<'static, TimedEvent>,
+    window_start: u64,
+    window_end: u64,
+}
+
+impl EventScheduler {
+    pub fn new(consumer: Consumer<'static, TimedEvent>) -> Self {
+        let now = Instant::now().as_ticks();
+        Self {
+            consumer,
+            window_start: now,
+            window_end: now,
+        }
+    }
+
+    pub fn begin_block(&mut self) {
+        self.window_start = self.window_end;
+        self.window_end = Instant::now().as_ticks();
+    }
+
+    pub fn next_due(&mut self, frames: usize) -> Option<(usize, MidiEvent)> {
+        let timed = *self.consumer.peek()?;
+        if timed.timestamp >= self.window_end {
+            return None;
+        }
+        self.consumer.dequeue();
+
+        let span = self.window_end - self.window_start;
+        let offset = if span == 0 || timed.timestamp <= self.window_start {
+            0
+        } else {
+            ((timed.timestamp - self.window_start) * frames as u64 / span) as usize
+        };
+        Some((offset.min(frames - 1), timed.event))
+    }
+}
@@ -1,5 +1,13 @@
-use crate::common::shared::{SystemCommand, COMMAND_CHANNEL, PRESET_CHANNEL};
+use crate::common::shared::{Patch, SystemCommand, COMMAND_CHANNEL, MAX_VOICES, PRESET_CHANNEL};
+use crate::control::events::{EventSender, MidiEvent};
+use crate::control::params::{CcMap, ParamId, ParamStore, PARAMS};
+use crate::control::voices::VoiceAllocator;
+use crate::data::presets::{NotePriority, Preset, TriggerMode, VoiceMode, VoiceSettings};
+use crate::data::settings::{
+    GlobalSettings, DEFAULT_MASTER_TUNE, MAX_MASTER_TUNE, MIN_MASTER_TUNE, OMNI,
+};
 use crate::data::storage::{Storage, MAGIC as STORAGE_MAGIC, VERSION as STORAGE_VERSION};
+use crate::data::tuning::{TuningTable, NOTE_COUNT, TUNING_SLOTS};
 use crate::usb::logger::{LED_SIGNAL_CHANNEL, MIDI_LOG_CHANNEL};
 use alloc::sync::Arc;
 use alloc::vec;
@@ -30,30 +38,60 @@ const NOTE_OFF: u8 = 0x80;
 const NOTE_ON: u8 = 0x90;
 const CONTROL_CHANGE: u8 = 0xB0;
 const PROGRAM_CHANGE: u8 = 0xC0;
+const POLY_PRESSURE: u8 = 0xA0;
+const CHANNEL_PRESSURE: u8 = 0xD0;
 const PITCH_BEND: u8 = 0xE0;
 const SYSEX_START: u8 = 0xF0;
 const SYSEX_END: u8 = 0xF7;
 
 const CC_MOD_WHEEL: u8 = 1;
-const CC_PORTAMENTO_TIME: u8 = 5;
+const CC_DATA_ENTRY: u8 = 6;
+const CC_DATA_ENTRY_LSB: u8 = 38;
 const CC_SUSTAIN: u8 = 64;
-const CC_FILTER_RESONANCE: u8 = 71;
-const CC_FILTER_CUTOFF: u8 = 74;
+const CC_LSB_OFFSET: u8 = 32;
+const CC_DATA_INCREMENT: u8 = 96;
+const CC_DATA_DECREMENT: u8 = 97;
+const CC_NRPN_LSB: u8 = 98;
+const CC_NRPN_MSB: u8 = 99;
+const CC_RPN_LSB: u8 = 100;
+const CC_RPN_MSB: u8 = 101;
 const CC_ALL_SOUND_OFF: u8 = 120;
 const CC_ALL_NOTES_OFF: u8 = 123;
 
+const RPN_BEND_RANGE: (u8, u8) = (0, 0);
+const RPN_NULL: (u8, u8) = (0x7F, 0x7F);
+const NRPN_PARAM_BANK: u8 = 0;
+
 const SYSEX_ID: u8 = 0x7D;
 const SYSEX_MODEL: u8 = 0x01;
 const CMD_DUMP_REQ: u8 = 0x01;
 const CMD_WRITE_REQ: u8 = 0x02;
 const CMD_WRITE_SUCCESS: u8 = 0x03;
 const CMD_WRITE_ERROR: u8 = 0x04;
+const CMD_SET_PARAM: u8 = 0x05;
+const CMD_GET_PARAM: u8 = 0x06;
+const CMD_PARAM_VALUE: u8 = 0x07;
+const CMD_SAVE_PRESET: u8 = 0x08;
+const CMD_LEARN: u8 = 0x09;
+const CMD_SET_CC: u8 = 0x0A;
+const CMD_SET_CHANNEL: u8 = 0x0B;
+const CMD_SET_TUNE: u8 = 0x0C;
+
+const MTS_NON_REALTIME: u8 = 0x7E;
+const MTS_REALTIME: u8 = 0x7F;
+const MTS_ALL_DEVICES: u8 = 0x7F;
+const MTS_SUB_ID: u8 = 0x08;
+const MTS_BULK_DUMP: u8 = 0x01;
+const MTS_NOTE_CHANGE: u8 = 0x02;
+const MTS_BULK_DUMP_LEN: usize = 408;
+const SYSEX_NONE: u8 = 0x7F;
 
 const ERR_BAD_LENGTH: u8 = 0x01;
 const ERR_BAD_MAGIC: u8 = 0x02;
+const ERR_BAD_INDEX: u8 = 0x03;
 
-fn midi_to_freq(note: u8) -> f32 {
-    440.0 * libm::powf(2.0, (note as f32 - 69.0) / 12.0)
+fn midi_to_freq(note: f32, a4: f32) -> f32 {
+    a4 * libm::powf(2.0, (note - 69.0) / 12.0)
 }
 
 struct NoteStack {
@@ -110,60 +148,311 @@ impl NoteStack {
         self.sustain_active = false;
     }
 
-    fn active_note(&self) -> Option<u8> {
-        self.notes.last().copied()
+    fn active_note(&self, priority: NotePriority) -> Option<u8> {
+        match priority {
+            NotePriority::Last => self.notes.last().copied(),
+            NotePriority::Low => self.notes.iter().min().copied(),
+            NotePriority::High => self.notes.iter().max().copied(),
+        }
     }
 }
 
-pub struct MidiControl {
+struct VoiceControl {
     target_freq_bits: AtomicU32,
     gate: AtomicBool,
     gate_reset: AtomicBool,
-    portamento_amount_bits: AtomicU32,
+    glide: AtomicBool,
+    velocity: AtomicU32,
+    pressure_bits: AtomicU32,
+    level_bits: AtomicU32,
+}
+
+impl VoiceControl {
+    const fn new() -> Self {
+        Self {
+            target_freq_bits: AtomicU32::new(440.0f32.to_bits()),
+            gate: AtomicBool::new(false),
+            gate_reset: AtomicBool::new(false),
+            glide: AtomicBool::new(true),
+            velocity: AtomicU32::new(127),
+            pressure_bits: AtomicU32::new(0),
+            level_bits: AtomicU32::new(0),
+        }
+    }
+}
+
+pub struct MidiControl {
+    voices: [VoiceControl; MAX_VOICES],
+    voice_mode: AtomicU32,
+    voice_count: AtomicU32,
+    voice_steal: AtomicU32,
+    voice_priority: AtomicU32,
+    voice_trigger: AtomicU32,
+    voice_limit: AtomicU32,
+    portamento_time_bits: AtomicU32,
+    portamento_mode: AtomicU32,
     pitch_bend_bits: AtomicU32,
+    bend_up_bits: AtomicU32,
+    bend_down_bits: AtomicU32,
     mod_wheel_bits: AtomicU32,
+    channel_pressure_bits: AtomicU32,
     parameter_1_bits: AtomicU32,
     parameter_2_bits: AtomicU32,
+    generation: AtomicU32,
+    fx_tails: AtomicBool,
+    master_tune_bits: AtomicU32,
+    tuning: AtomicU32,
+    tunings: [[AtomicU32; NOTE_COUNT]; TUNING_SLOTS],
 }
 
 impl MidiControl {
     pub fn new() -> Self {
         Self {
-            target_freq_bits: AtomicU32::new(440.0f32.to_bits()),
-            gate: AtomicBool::new(false),
-            gate_reset: AtomicBool::new(false),
-            portamento_amount_bits: AtomicU32::new(0.0f32.to_bits()),
-            pitch_bend_bits: AtomicU32::new(1.0f32.to_bits()),
+            voices: [const { VoiceControl::new() }; MAX_VOICES],
+            voice_mode: AtomicU32::new(0),
+            voice_count: AtomicU32::new(1),
+            voice_steal: AtomicU32::new(0),
+            voice_priority: AtomicU32::new(0),
+            voice_trigger: AtomicU32::new(0),
+            voice_limit: AtomicU32::new(MAX_VOICES as u32),
+            portamento_time_bits: AtomicU32::new(0.0f32.to_bits()),
+            portamento_mode: AtomicU32::new(0),
+            pitch_bend_bits: AtomicU32::new(0.0f32.to_bits()),
+            bend_up_bits: AtomicU32::new(2.0f32.to_bits()),
+            bend_down_bits: AtomicU32::new(2.0f32.to_bits()),
             mod_wheel_bits: AtomicU32::new(0.0f32.to_bits()),
+            channel_pressure_bits: AtomicU32::new(0.0f32.to_bits()),
             parameter_1_bits: AtomicU32::new(0.5f32.to_bits()),
             parameter_2_bits: AtomicU32::new(0.0f32.to_bits()),
+            generation: AtomicU32::new(0),
+            fx_tails: AtomicBool::new(true),
+            master_tune_bits: AtomicU32::new(DEFAULT_MASTER_TUNE.to_bits()),
+            tuning: AtomicU32::new(0),
+            tunings: core::array::from_fn(|_| {
+                core::array::from_fn(|n| AtomicU32::new((n as f32).to_bits()))
+            }),
         }
     }
 
-    pub fn set_freq(&self, freq: f32) {
-        self.target_freq_bits
+    pub fn set_freq(&self, voice: usize, freq: f32) {
+        self.voices[voice]
+            .target_freq_bits
             .store(freq.to_bits(), Ordering::Relaxed);
     }
 
-    pub fn set_gate(&self, gate: bool) {
-        self.gate.store(gate, Ordering::Relaxed);
+    pub fn set_gate(&self, voice: usize, gate: bool) {
+        let v = &self.voices[voice];
+        v.gate.store(gate, Ordering::Relaxed);
         if !gate {
-            self.gate_reset.store(true, Ordering::Relaxed);
+            v.gate_reset.store(true, Ordering::Relaxed);
+        }
+    }
+
+    pub fn retrigger(&self, voice: usize) {
+        let v = &self.voices[voice];
+        v.gate_reset.store(true, Ordering::Relaxed);
+        v.gate.store(true, Ordering::Relaxed);
+    }
+
+    pub fn set_glide(&self, voice: usize, glide: bool) {
+        self.voices[voice].glide.store(glide, Ordering::Relaxed);
+    }
+
+    pub fn should_glide(&self, voice: usize) -> bool {
+        self.voices[voice].glide.load(Ordering::Relaxed)
+    }
+
+    pub fn set_velocity(&self, voice: usize, velocity: u8) {
+        self.voices[voice]
+            .velocity
+            .store(velocity as u32, Ordering::Relaxed);
+    }
+
+    pub fn get_velocity(&self, voice: usize) -> f32 {
+        self.voices[voice].velocity.load(Ordering::Relaxed) as f32 / 127.0
+    }
+
+    pub fn apply_event(&self, event: &MidiEvent) {
+        match *event {
+            MidiEvent::NoteOn {
+                voice,
+                freq,
+                velocity,
+                retrigger,
+            } => {
+                let voice = voice as usize;
+                let legato = self.get_gate(voice) > 0.0;
+                let mode = self.get_voice_settings().get_trigger_mode();
+                self.set_glide(voice, legato || mode != TriggerMode::LegatoGlide);
+                self.set_freq(voice, freq);
+                self.set_velocity(voice, velocity);
+                self.set_poly_pressure(voice, 0.0);
+                if retrigger || (legato && mode == TriggerMode::Retrigger) {
+                    self.retrigger(voice);
+                } else {
+                    self.set_gate(voice, true);
+                }
+            }
+            MidiEvent::NoteOff { voice } => self.set_gate(voice as usize, false),
+            MidiEvent::Glide { voice, freq } => {
+                let voice = voice as usize;
+                let changed = self.get_target_freq(voice) != freq;
+                self.set_glide(voice, true);
+                self.set_freq(voice, freq);
+                if changed && self.get_voice_settings().get_trigger_mode() == TriggerMode::Retrigger
+                {
+                    self.retrigger(voice);
+                }
+            }
+            MidiEvent::PitchBend(bend) => self.set_pitch_bend(bend),
+            MidiEvent::BendRange(up, down) => self.set_bend_range(up, down),
+            MidiEvent::ChannelPressure(value) => self.set_channel_pressure(value),
+            MidiEvent::PolyPressure { voice, value } => {
+                self.set_poly_pressure(voice as usize, value)
+            }
+            MidiEvent::ModWheel(value) => self.set_mod_wheel(value),
+            MidiEvent::Portamento(time, mode) => self.set_portamento(time, mode),
+            MidiEvent::Cutoff(value) => self.set_parameter_1(value),
+            MidiEvent::Resonance(value) => self.set_parameter_2(value),
+            MidiEvent::AllNotesOff => self.reset(),
+        }
+    }
+
+    pub fn take_gate_reset(&self, voice: usize) -> bool {
+        self.voices[voice].gate_reset.swap(false, Ordering::Relaxed)
+    }
+
+    pub fn set_voice_level(&self, voice: usize, level: f32) {
+        self.voices[voice]
+            .level_bits
+            .store(level.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn get_voice_level(&self, voice: usize) -> f32 {
+        f32::from_bits(self.voices[voice].level_bits.load(Ordering::Relaxed))
+    }
+
+    pub fn is_voice_active(&self, voice: usize) -> bool {
+        let v = &self.voices[voice];
+        v.gate.load(Ordering::Relaxed)
+            || v.gate_reset.load(Ordering::Relaxed)
+            || f32::from_bits(v.level_bits.load(Ordering::Relaxed)) > 0.0
+    }
+
+    pub fn set_voice_config(&self, settings: &VoiceSettings) {
+        self.voice_mode.store(settings.mode, Ordering::Relaxed);
+        self.voice_count
+            .store(settings.voice_count() as u32, Ordering::Relaxed);
+        self.voice_steal.store(settings.steal, Ordering::Relaxed);
+        self.voice_priority
+            .store(settings.priority, Ordering::Relaxed);
+        self.voice_trigger
+            .store(settings.trigger, Ordering::Relaxed);
+    }
+
+    pub fn get_voice_settings(&self) -> VoiceSettings {
+        VoiceSettings {
+            mode: self.voice_mode.load(Ordering::Relaxed),
+            count: self.voice_count.load(Ordering::Relaxed),
+            steal: self.voice_steal.load(Ordering::Relaxed),
+            priority: self.voice_priority.load(Ordering::Relaxed),
+            trigger: self.voice_trigger.load(Ordering::Relaxed),
         }
     }
 
-    pub fn take_gate_reset(&self) -> bool {
-        self.gate_reset.swap(false, Ordering::Relaxed)
+    pub fn set_voice_limit(&self, limit: usize) {
+        self.voice_limit
+            .store(limit.clamp(1, MAX_VOICES) as u32, Ordering::Relaxed);
+    }
+
+    pub fn available_voices(&self) -> usize {
+        let limit = self.voice_limit.load(Ordering::Relaxed) as usize;
+        self.get_voice_settings().voice_count().min(limit)
+    }
+
+    pub fn apply_params(&self, params: &ParamStore) {
+        let cutoff = ParamId::FilterCutoff.def();
+        self.set_parameter_1(cutoff.normalize(params.get(ParamId::FilterCutoff)));
+        let resonance = ParamId::FilterResonance.def();
+        self.set_parameter_2(resonance.normalize(params.get(ParamId::FilterResonance)));
+        self.set_portamento(
+            params.get(ParamId::Portamento),
+            params.get(ParamId::PortamentoMode) as u32,
+        );
+        self.set_bend_range(params.get(ParamId::BendUp), params.get(ParamId::BendDown));
+        self.select_tuning(params.get(ParamId::Tuning) as u32);
+    }
+
+    pub fn set_generation(&self, generation: u32) {
+        self.generation.store(generation, Ordering::Relaxed);
+    }
+
+    pub fn get_generation(&self) -> u32 {
+        self.generation.load(Ordering::Relaxed)
     }
 
-    pub fn set_portamento(&self, amount: f32) {
-        self.portamento_amount_bits
-            .store(amount.to_bits(), Ordering::Relaxed);
+    pub fn set_fx_tails(&self, enabled: bool) {
+        self.fx_tails.store(enabled, Ordering::Relaxed);
     }
 
-    pub fn set_pitch_bend(&self, bend_factor: f32) {
+    pub fn get_fx_tails(&self) -> bool {
+        self.fx_tails.load(Ordering::Relaxed)
+    }
+
+    pub fn set_master_tune(&self, a4: f32) {
+        self.master_tune_bits.store(a4.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn select_tuning(&self, tuning: u32) {
+        self.tuning.store(tuning, Ordering::Relaxed);
+    }
+
+    pub fn get_tuning(&self, slot: usize) -> Option<TuningTable> {
+        let notes = self.tunings.get(slot)?;
+        let mut table = TuningTable::equal();
+        for (pitch, n) in table.notes.iter_mut().zip(notes.iter()) {
+            *pitch = f32::from_bits(n.load(Ordering::Relaxed));
+        }
+        Some(table)
+    }
+
+    pub fn set_tuning(&self, slot: usize, table: &TuningTable) {
+        if let Some(notes) = self.tunings.get(slot) {
+            for (n, pitch) in notes.iter().zip(table.notes.iter()) {
+                n.store(pitch.to_bits(), Ordering::Relaxed);
+            }
+        }
+    }
+
+    pub fn note_freq(&self, note: u8) -> f32 {
+        let pitch = match self.tuning.load(Ordering::Relaxed) as usize {
+            0 => note as f32,
+            t => self
+                .tunings
+                .get(t - 1)
+                .and_then(|notes| notes.get(note as usize))
+                .map_or(note as f32, |n| f32::from_bits(n.load(Ordering::Relaxed))),
+        };
+        midi_to_freq(
+            pitch,
+            f32::from_bits(self.master_tune_bits.load(Ordering::Relaxed)),
+        )
+    }
+
+    pub fn set_portamento(&self, time: f32, mode: u32) {
+        self.portamento_time_bits
+            .store(time.to_bits(), Ordering::Relaxed);
+        self.portamento_mode.store(mode, Ordering::Relaxed);
+    }
+
+    pub fn set_pitch_bend(&self, bend: f32) {
         self.pitch_bend_bits
-            .store(bend_factor.to_bits(), Ordering::Relaxed);
+            .store(bend.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn set_bend_range(&self, up: f32, down: f32) {
+        self.bend_up_bits.store(up.to_bits(), Ordering::Relaxed);
+        self.bend_down_bits.store(down.to_bits(), Ordering::Relaxed);
     }
 
     pub fn set_mod_wheel(&self, value: f32) {
@@ -171,6 +460,17 @@ impl MidiControl {
             .store(value.to_bits(), Ordering::Relaxed);
     }
 
+    pub fn set_channel_pressure(&self, value: f32) {
+        self.channel_pressure_bits
+            .store(value.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn set_poly_pressure(&self, voice: usize, value: f32) {
+        self.voices[voice]
+            .pressure_bits
+            .store(value.to_bits(), Ordering::Relaxed);
+    }
+
     pub fn set_parameter_1(&self, value: f32) {
         self.parameter_1_bits
             .store(value.to_bits(), Ordering::Relaxed);
@@ -182,31 +482,59 @@ impl MidiControl {
     }
 
     pub fn reset(&self) {
-        self.gate.store(false, Ordering::Relaxed);
-        self.gate_reset.store(false, Ordering::Relaxed);
+        for v in self.voices.iter() {
+            v.gate.store(false, Ordering::Relaxed);
+            v.gate_reset.store(false, Ordering::Relaxed);
+            v.pressure_bits.store(0.0f32.to_bits(), Ordering::Relaxed);
+        }
+        self.channel_pressure_bits
+            .store(0.0f32.to_bits(), Ordering::Relaxed);
         self.pitch_bend_bits
-            .store(1.0f32.to_bits(), Ordering::Relaxed);
+            .store(0.0f32.to_bits(), Ordering::Relaxed);
         self.mod_wheel_bits
             .store(0.0f32.to_bits(), Ordering::Relaxed);
     }
 
-    pub fn get_target_freq(&self) -> f32 {
-        f32::from_bits(self.target_freq_bits.load(Ordering::Relaxed))
+    pub fn get_target_freq(&self, voice: usize) -> f32 {
+        f32::from_bits(self.voices[voice].target_freq_bits.load(Ordering::Relaxed))
     }
 
-    pub fn get_portamento_amount(&self) -> f32 {
-        f32::from_bits(self.portamento_amount_bits.load(Ordering::Relaxed))
+    pub fn get_portamento_time(&self) -> f32 {
+        f32::from_bits(self.portamento_time_bits.load(Ordering::Relaxed))
     }
 
-    pub fn get_pitch_bend(&self) -> f32 {
+    pub fn is_constant_time_glide(&self) -> bool {
+        self.portamento_mode.load(Ordering::Relaxed) == 1
+    }
+
+    pub fn get_bend(&self) -> f32 {
         f32::from_bits(self.pitch_bend_bits.load(Ordering::Relaxed))
     }
 
-    #[allow(dead_code)]
+    pub fn get_pitch_bend(&self) -> f32 {
+        let bend = self.get_bend();
+        if bend == 0.0 {
+            return 1.0;
+        }
+        let range = if bend > 0.0 {
+            &self.bend_up_bits
+        } else {
+            &self.bend_down_bits
+        };
+        let semitones = bend * f32::from_bits(range.load(Ordering::Relaxed));
+        libm::powf(2.0, semitones / 12.0)
+    }
+
     pub fn get_mod_wheel(&self) -> f32 {
         f32::from_bits(self.mod_wheel_bits.load(Ordering::Relaxed))
     }
 
+    pub fn get_pressure(&self, voice: usize) -> f32 {
+        let channel = f32::from_bits(self.channel_pressure_bits.load(Ordering::Relaxed));
+        let poly = f32::from_bits(self.voices[voice].pressure_bits.load(Ordering::Relaxed));
+        channel.max(poly)
+    }
+
     pub fn get_parameter_1(&self) -> f32 {
         f32::from_bits(self.parameter_1_bits.load(Ordering::Relaxed))
     }
@@ -215,8 +543,8 @@ impl MidiControl {
         f32::from_bits(self.parameter_2_bits.load(Ordering::Relaxed))
     }
 
-    pub fn get_gate(&self) -> f32 {
-        if self.gate.load(Ordering::Relaxed) {
+    pub fn get_gate(&self, voice: usize) -> f32 {
+        if self.voices[voice].gate.load(Ordering::Relaxed) {
             1.0
         } else {
             0.0
@@ -226,49 +554,86 @@ impl MidiControl {
 
 pub struct MidiFreq {
     control: Arc<MidiControl>,
+    voice: usize,
     current_freq: f32,
+    last_target: f32,
+    glide_ratio: f32,
+    sample_rate: f32,
 }
 
 impl MidiFreq {
-    pub fn new(control: Arc<MidiControl>) -> Self {
-        let initial_freq = control.get_target_freq();
+    pub fn new(control: Arc<MidiControl>, voice: usize) -> Self {
+        let initial_freq = control.get_target_freq(voice);
         Self {
             control,
+            voice,
             current_freq: initial_freq,
+            last_target: initial_freq,
+            glide_ratio: 1.0,
+            sample_rate: 44100.0,
+        }
+    }
+
+    fn ratio(&mut self, target: f32, time: f32) -> f32 {
+        let samples = time * self.sample_rate;
+        if self.control.is_constant_time_glide() {
+            if target != self.last_target {
+                let octaves = libm::log2f(target / self.current_freq);
+                self.glide_ratio = libm::exp2f(octaves / samples);
+            }
+            self.glide_ratio
+        } else if target > self.current_freq {
+            libm::exp2f(1.0 / samples)
+        } else {
+            libm::exp2f(-1.0 / samples)
         }
     }
 }
 
 impl FrameProcessor<Mono> for MidiFreq {
     fn process(&mut self, buffer: &mut [f32], _frame_index: u64) {
-        let target = self.control.get_target_freq();
-        let amount = self.control.get_portamento_amount();
+        let target = self.control.get_target_freq(self.voice);
+        let time = self.control.get_portamento_time();
         let bend = self.control.get_pitch_bend();
 
-        const CHUNK_SIZE: usize = 32;
-        let factor = 1.0 - amount.clamp(0.0, 0.999);
-
-        for chunk in buffer.chunks_mut(CHUNK_SIZE) {
-            let diff = target - self.current_freq;
+        if !self.control.should_glide(self.voice) || time <= 0.0 || self.current_freq <= 0.0 {
+            self.current_freq = target;
+        }
 
-            if diff.abs() < 0.1 {
-                self.current_freq = target;
-            } else {
-                self.current_freq += diff * factor;
-            }
+        let ratio = if self.current_freq != target {
+            self.ratio(target, time)
+        } else {
+            1.0
+        };
+        self.last_target = target;
 
-            let final_freq = self.current_freq * bend;
+        if self.current_freq == target || ratio == 1.0 {
+            self.current_freq = target;
+            buffer.fill(target * bend);
+            return;
+        }
 
-            for sample in chunk.iter_mut() {
-                *sample = final_freq;
+        let rising = ratio > 1.0;
+        for sample in buffer.iter_mut() {
+            if self.current_freq != target {
+                self.current_freq *= ratio;
+                if (rising && self.current_freq >= target)
+                    || (!rising && self.current_freq <= target)
+                {
+                    self.current_freq = target;
+                }
             }
+            *sample = self.current_freq * bend;
         }
     }
 
-    fn set_sample_rate(&mut self, _sample_rate: f32) {}
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+    }
 
     fn reset(&mut self) {
-        self.current_freq = self.control.get_target_freq();
+        self.current_freq = self.control.get_target_freq(self.voice);
+        self.last_target = self.current_freq;
     }
 
     fn latency_samples(&self) -> u32 {
@@ -282,11 +647,11 @@ impl FrameProcessor<Mono> for MidiFreq {
     }
 }
 
-pub struct MidiGate(pub Arc<MidiControl>);
+pub struct MidiGate(pub Arc<MidiControl>, pub usize);
 impl FrameProcessor<Mono> for MidiGate {
     fn process(&mut self, buffer: &mut [f32], _frame_index: u64) {
-        let g = self.0.get_gate();
-        let reset = self.0.take_gate_reset();
+        let g = self.0.get_gate(self.1);
+        let reset = self.0.take_gate_reset(self.1);
 
         for (i, sample) in buffer.iter_mut().enumerate() {
             if reset && i < 4 {
@@ -313,7 +678,7 @@ pub struct MidiFilterCutoff(pub Arc<MidiControl>);
 impl FrameProcessor<Mono> for MidiFilterCutoff {
     fn process(&mut self, buffer: &mut [f32], _frame_index: u64) {
         let val = self.0.get_parameter_1();
-        let freq = 20.0 * libm::powf(1000.0, val);
+        let freq = ParamId::FilterCutoff.def().denormalize(val);
         for sample in buffer.iter_mut() {
             *sample = freq;
         }
@@ -335,7 +700,7 @@ pub struct MidiFilterResonance(pub Arc<MidiControl>);
 impl FrameProcessor<Mono> for MidiFilterResonance {
     fn process(&mut self, buffer: &mut [f32], _frame_index: u64) {
         let val = self.0.get_parameter_2();
-        let q = 0.707 + (val * 9.3);
+        let q = ParamId::FilterResonance.def().denormalize(val);
         for sample in buffer.iter_mut() {
             *sample = q;
         }
@@ -353,17 +718,390 @@ impl FrameProcessor<Mono> for MidiFilterResonance {
     }
 }
 
+fn note_on(
+    note: u8,
+    velocity: u8,
+    notes: &mut NoteStack,
+    voices: &mut VoiceAllocator,
+    control: &MidiControl,
+    events: &mut EventSender,
+) {
+    let settings = control.get_voice_settings();
+    let freq = control.note_freq(note);
+    match settings.get_mode() {
+        VoiceMode::Mono => {
+            notes.note_on(note);
+            if notes.active_note(settings.get_priority()) == Some(note) {
+                events.send(MidiEvent::NoteOn {
+                    voice: 0,
+                    freq,
+                    velocity,
+                    retrigger: false,
+                });
+            }
+        }
+        VoiceMode::Poly => {
+            let (voice, stolen) = voices.note_on(
+                note,
+                control.available_voices(),
+                settings.get_steal_mode(),
+                control,
+            );
+            events.send(MidiEvent::NoteOn {
+                voice: voice as u8,
+                freq,
+                velocity,
+                retrigger: stolen,
+            });
+        }
+    }
+    let _ = LED_SIGNAL_CHANNEL.try_send(true);
+}
+
+fn update_mono_voice(notes: &NoteStack, control: &MidiControl, events: &mut EventSender) {
+    let priority = control.get_voice_settings().get_priority();
+    if let Some(note) = notes.active_note(priority) {
+        events.send(MidiEvent::Glide {
+            voice: 0,
+            freq: control.note_freq(note),
+        });
+    } else {
+        events.send(MidiEvent::NoteOff { voice: 0 });
+        let _ = LED_SIGNAL_CHANNEL.try_send(false);
+    }
+}
+
+fn note_off(
+    note: u8,
+    notes: &mut NoteStack,
+    voices: &mut VoiceAllocator,
+    control: &MidiControl,
+    events: &mut EventSender,
+) {
+    match control.get_voice_settings().get_mode() {
+        VoiceMode::Mono => {
+            notes.note_off(note);
+            update_mono_voice(notes, control, events);
+        }
+        VoiceMode::Poly => {
+            if let Some(voice) = voices.note_off(note) {
+                events.send(MidiEvent::NoteOff { voice: voice as u8 });
+            }
+            if !voices.any_active() {
+                let _ = LED_SIGNAL_CHANNEL.try_send(false);
+            }
+        }
+    }
+}
+
+fn set_sustain(
+    active: bool,
+    notes: &mut NoteStack,
+    voices: &mut VoiceAllocator,
+    control: &MidiControl,
+    events: &mut EventSender,
+) {
+    notes.set_sustain(active);
+    let released = voices.set_sustain(active);
+    if active {
+        return;
+    }
+    match control.get_voice_settings().get_mode() {
+        VoiceMode::Mono => update_mono_voice(notes, control, events),
+        VoiceMode::Poly => {
+            for voice in released {
+                events.send(MidiEvent::NoteOff { voice: voice as u8 });
+            }
+            if !voices.any_active() {
+                let _ = LED_SIGNAL_CHANNEL.try_send(false);
+            }
+        }
+    }
+}
+
+fn apply_voice_config(
+    settings: &VoiceSettings,
+    notes: &mut NoteStack,
+    voices: &mut VoiceAllocator,
+    control: &MidiControl,
+    events: &mut EventSender,
+) {
+    let current = control.get_voice_settings();
+    if current.mode != settings.mode || current.count != settings.voice_count() as u32 {
+        notes.clear();
+        voices.clear();
+        events.send(MidiEvent::AllNotesOff);
+        let _ = LED_SIGNAL_CHANNEL.try_send(false);
+    }
+    control.set_voice_config(settings);
+}
+
+fn rebuild_synth(patch: &Patch) {
+    let _ = PRESET_CHANNEL.try_receive();
+    let _ = PRESET_CHANNEL.try_send(patch.clone());
+}
+
+fn load_preset(
+    preset: Preset,
+    patch: &mut Patch,
+    notes: &mut NoteStack,
+    voices: &mut VoiceAllocator,
+    control: &MidiControl,
+    events: &mut EventSender,
+) {
+    patch.preset = preset;
+    patch.params = Arc::new(ParamStore::new(&preset));
+    apply_voice_config(&preset.voice, notes, voices, control, events);
+    rebuild_synth(patch);
+}
+
+fn set_parameter(
+    id: ParamId,
+    value: f32,
+    patch: &mut Patch,
+    notes: &mut NoteStack,
+    voices: &mut VoiceAllocator,
+    control: &MidiControl,
+    events: &mut EventSender,
+) {
+    let def = id.def();
+    def.set(&mut patch.preset, value);
+    let value = def.get(&patch.preset);
+    patch.params.set(id, value);
+    patch.fx_params.set(id, value);
+    log_midi!("PARAM {} = {:.3}{}", def.name, value, def.unit);
+
+    match id {
+        ParamId::FilterCutoff => events.send(MidiEvent::Cutoff(def.normalize(value))),
+        ParamId::FilterResonance => events.send(MidiEvent::Resonance(def.normalize(value))),
+        ParamId::Portamento | ParamId::PortamentoMode => events.send(MidiEvent::Portamento(
+            patch.preset.portamento,
+            patch.preset.portamento_mode,
+        )),
+        ParamId::BendUp | ParamId::BendDown => events.send(MidiEvent::BendRange(
+            ParamId::BendUp.def().get(&patch.preset),
+            ParamId::BendDown.def().get(&patch.preset),
+        )),
+        ParamId::VoiceMode | ParamId::VoiceCount => {
+            apply_voice_config(&patch.preset.voice, notes, voices, control, events);
+            rebuild_synth(patch);
+        }
+        ParamId::VoiceSteal | ParamId::VoicePriority | ParamId::VoiceTrigger => {
+            apply_voice_config(&patch.preset.voice, notes, voices, control, events)
+        }
+        ParamId::Tuning => control.select_tuning(patch.preset.tuning),
+        _ => {}
+    }
+}
+
+fn encode_14bit(norm: f32) -> (u8, u8) {
+    let value = (norm.clamp(0.0, 1.0) * 16383.0) as u16;
+    (((value >> 7) & 0x7F) as u8, (value & 0x7F) as u8)
+}
+
+fn decode_14bit(msb: u8, lsb: u8) -> f32 {
+    ((((msb & 0x7F) as u16) << 7) | (lsb & 0x7F) as u16) as f32 / 16383.0
+}
+
+async fn send_sysex(sender: &mut Sender<'static, Driver<'static, USB>>, payload: &[u8]) {
+    let mut msg: heapless::Vec<u8, 32> = heapless::Vec::new();
+    let _ = msg.extend_from_slice(&[SYSEX_START, SYSEX_ID, SYSEX_MODEL]);
+    let _ = msg.extend_from_slice(payload);
+    let _ = msg.push(SYSEX_END);
+
+    let mut chunks = msg.chunks(3).peekable();
+    while let Some(chunk) = chunks.next() {
+        let last = chunks.peek().is_none();
+        let cin = match (last, chunk.len()) {
+            (false, _) => 0x04,
+            (true, 1) => 0x05,
+            (true, 2) => 0x06,
+            _ => 0x07,
+        };
+        let mut packet = [cin, 0, 0, 0];
+        packet[1..1 + chunk.len()].copy_from_slice(chunk);
+        let _ = sender.write_packet(&packet).await;
+    }
+}
+
+async fn list_parameters(preset: &Preset, cc_map: &CcMap) {
+    for def in PARAMS.iter() {
+        let mut msg = heapless::String::<64>::new();
+        let written = match cc_map.cc_for(def.id) {
+            Some(cc) => core::fmt::write(
+                &mut msg,
+                format_args!(
+                    "{:>2} {:<18} {:>9.3} {:<3} ({}) CC{}\r\n",
+                    def.id as u8,
+                    def.name,
+                    def.get(preset),
+                    def.unit,
+                    def.default,
+                    cc
+                ),
+            ),
+            None => core::fmt::write(
+                &mut msg,
+                format_args!(
+                    "{:>2} {:<18} {:>9.3} {:<3} ({})\r\n",
+                    def.id as u8,
+                    def.name,
+                    def.get(preset),
+                    def.unit,
+                    def.default
+                ),
+            ),
+        };
+        if written.is_ok() {
+            MIDI_LOG_CHANNEL.send(msg).await;
+        }
+    }
+}
+
+async fn set_channel(channel: u8, settings: &mut GlobalSettings, storage: &mut Storage<'static>) {
+    if channel > 16 {
+        log_midi!("Invalid MIDI channel {}\r\n", channel);
+        return;
+    }
+    if channel == OMNI {
+        log_midi!("MIDI channel: omni\r\n");
+    } else {
+        log_midi!("MIDI channel: {}\r\n", channel);
+    }
+    settings.midi_channel = channel;
+    storage.save_settings(settings).await;
+}
+
+async fn set_master_tune(
+    a4: f32,
+    settings: &mut GlobalSettings,
+    storage: &mut Storage<'static>,
+    control: &MidiControl,
+) {
+    if !(MIN_MASTER_TUNE..=MAX_MASTER_TUNE).contains(&a4) {
+        log_midi!("Invalid master tune {:.1} Hz\r\n", a4);
+        return;
+    }
+    log_midi!("Master tune: A4 = {:.1} Hz\r\n", a4);
+    settings.master_tune = a4;
+    control.set_master_tune(a4);
+    storage.save_settings(settings).await;
+}
+
+async fn load_tunings(storage: &mut Storage<'static>, control: &MidiControl) {
+    for slot in 0..TUNING_SLOTS {
+        if let Some(table) = storage.load_tuning(slot).await {
+            control.set_tuning(slot, &table);
+        }
+    }
+}
+
+async fn receive_tuning(
+    msg: &[u8],
+    settings: &GlobalSettings,
+    storage: &mut Storage<'static>,
+    control: &MidiControl,
+) {
+    let device = settings.midi_channel.saturating_sub(1);
+    if msg[2] != device && msg[2] != MTS_ALL_DEVICES {
+        return;
+    }
+
+    match (msg[1], msg[4]) {
+        (MTS_NON_REALTIME, MTS_BULK_DUMP) if msg.len() == MTS_BULK_DUMP_LEN => {
+            let slot = msg[5] as usize;
+            let checksum = msg[1..MTS_BULK_DUMP_LEN - 2]
+                .iter()
+                .fold(0u8, |sum, b| sum ^ b)
+                & 0x7F;
+            if checksum != msg[MTS_BULK_DUMP_LEN - 2] {
+                log_midi!("MTS: Bad checksum for tuning {}\r\n", slot);
+                return;
+            }
+
+            let mut table = TuningTable::equal();
+            table.name.copy_from_slice(&msg[6..22]);
+            for (note, data) in msg[22..MTS_BULK_DUMP_LEN - 2].chunks(3).enumerate() {
+                table.set_note(note as u8, data);
+            }
+            if storage.save_tuning(slot, &table).await {
+                control.set_tuning(slot, &table);
+            }
+        }
+        (MTS_REALTIME, MTS_NOTE_CHANGE) if msg.len() >= 8 => {
+            let slot = msg[5] as usize;
+            let count = msg[6] as usize;
+            let Some(mut table) = control.get_tuning(slot) else {
+                return;
+            };
+            for change in msg[7..msg.len() - 1].chunks_exact(4).take(count) {
+                table.set_note(change[0], &change[1..]);
+            }
+            control.set_tuning(slot, &table);
+            log_midi!("MTS: Retuned {} notes in tuning {}\r\n", count, slot);
+        }
+        _ => {}
+    }
+}
+
+fn is_reserved_cc(cc: u8) -> bool {
+    matches!(
+        cc,
+        CC_MOD_WHEEL
+            | CC_DATA_ENTRY
+            | CC_SUSTAIN
+            | CC_DATA_INCREMENT
+            | CC_DATA_DECREMENT
+            | CC_NRPN_LSB
+            | CC_NRPN_MSB
+            | CC_RPN_LSB
+            | CC_RPN_MSB
+    ) || (CC_LSB_OFFSET..2 * CC_LSB_OFFSET).contains(&cc)
+        || cc >= CC_ALL_SOUND_OFF
+}
+
+async fn assign_cc(
+    id: ParamId,
+    cc: Option<u8>,
+    settings: &mut GlobalSettings,
+    storage: &mut Storage<'static>,
+) {
+    let name = id.def().name;
+    match cc {
+        Some(cc) if is_reserved_cc(cc) => {
+            log_midi!("CC{} is reserved\r\n", cc);
+            return;
+        }
+        Some(cc) => log_midi!("LEARN {} -> CC{}\r\n", name, cc),
+        None => log_midi!("UNLEARN {}\r\n", name),
+    }
+    settings.cc_map.assign(id, cc);
+    storage.save_settings(settings).await;
+}
+
 #[embassy_executor::task]
 pub async fn midi_task(
     mut receiver: Receiver<'static, Driver<'static, USB>>,
     mut sender: Sender<'static, Driver<'static, USB>>,
     midi_control: Arc<MidiControl>,
     mut storage: Storage<'static>,
+    mut events: EventSender,
+    initial_patch: Patch,
 ) {
     let mut buf = [0; 64];
     let mut notes = NoteStack::new();
+    let mut voices = VoiceAllocator::new();
 
     let mut current_preset_index = 4;
+    let mut patch = initial_patch;
+    let mut settings = storage.load_settings().await;
+    midi_control.set_master_tune(settings.master_tune);
+    load_tunings(&mut storage, &midi_control).await;
+    let mut learning: Option<ParamId> = None;
+    let mut rpn = RPN_NULL;
+    let mut nrpn = RPN_NULL;
+    let mut data_msb = 0u8;
+    let mut cc_msb = [0u8; CC_LSB_OFFSET as usize];
 
     let mut sysex_buf = vec![0u8; 8192 + 32];
     let mut sysex_idx = 0;
@@ -417,200 +1155,148 @@ pub async fn midi_task(
                                         if msg.len() >= 5
                                             && msg[0] == SYSEX_START
                                             && msg[msg.len() - 1] == SYSEX_END
+                                            && msg[1] == SYSEX_ID
+                                            && msg[2] == SYSEX_MODEL
                                         {
-                                            if msg[1] == SYSEX_ID && msg[2] == SYSEX_MODEL {
-                                                let cmd = msg[3];
-                                                match cmd {
-                                                    CMD_DUMP_REQ => {
-                                                        log_midi!("SysEx: Dump Request\r\n");
-                                                        let mut raw_data = vec![0u8; 4096];
-                                                        storage.read_raw(&mut raw_data).await;
-
-                                                        let p1 = [
-                                                            0x04,
-                                                            SYSEX_START,
-                                                            SYSEX_ID,
-                                                            SYSEX_MODEL,
-                                                        ];
-                                                        let _ = sender.write_packet(&p1).await;
-
-                                                        let h0 = (raw_data[0] >> 4) & 0x0F;
-                                                        let l0 = raw_data[0] & 0x0F;
-                                                        let p2 = [0x04, CMD_WRITE_REQ, h0, l0];
-                                                        let _ = sender.write_packet(&p2).await;
-
-                                                        let mut encoded_buf = vec![0u8; 8190];
-                                                        let mut enc_idx = 0;
-                                                        for byte in raw_data.iter().skip(1) {
-                                                            encoded_buf[enc_idx] =
-                                                                (byte >> 4) & 0x0F;
-                                                            encoded_buf[enc_idx + 1] = byte & 0x0F;
-                                                            enc_idx += 2;
-                                                        }
+                                            let cmd = msg[3];
+                                            match cmd {
+                                                CMD_DUMP_REQ => {
+                                                    log_midi!("SysEx: Dump Request\r\n");
+                                                    let mut raw_data = vec![0u8; 4096];
+                                                    storage.read_raw(&mut raw_data).await;
+
+                                                    let p1 =
+                                                        [0x04, SYSEX_START, SYSEX_ID, SYSEX_MODEL];
+                                                    let _ = sender.write_packet(&p1).await;
+
+                                                    let h0 = (raw_data[0] >> 4) & 0x0F;
+                                                    let l0 = raw_data[0] & 0x0F;
+                                                    let p2 = [0x04, CMD_WRITE_REQ, h0, l0];
+                                                    let _ = sender.write_packet(&p2).await;
+
+                                                    let mut encoded_buf = vec![0u8; 8190];
+                                                    let mut enc_idx = 0;
+                                                    for byte in raw_data.iter().skip(1) {
+                                                        encoded_buf[enc_idx] = (byte >> 4) & 0x0F;
+                                                        encoded_buf[enc_idx + 1] = byte & 0x0F;
+                                                        enc_idx += 2;
+                                                    }
 
-                                                        let mut i = 0;
-                                                        let mut f7_sent = false;
-                                                        let mut packet_count = 2;
-
-                                                        while i < encoded_buf.len() {
-                                                            let remaining = encoded_buf.len() - i;
-                                                            if remaining >= 3 {
-                                                                let _ = sender
-                                                                    .write_packet(&[
-                                                                        0x04,
-                                                                        encoded_buf[i],
-                                                                        encoded_buf[i + 1],
-                                                                        encoded_buf[i + 2],
-                                                                    ])
-                                                                    .await;
-                                                                i += 3;
-                                                                packet_count += 1;
+                                                    let mut i = 0;
+                                                    let mut f7_sent = false;
+                                                    let mut packet_count = 2;
+
+                                                    while i < encoded_buf.len() {
+                                                        let remaining = encoded_buf.len() - i;
+                                                        if remaining >= 3 {
+                                                            let _ = sender
+                                                                .write_packet(&[
+                                                                    0x04,
+                                                                    encoded_buf[i],
+                                                                    encoded_buf[i + 1],
+                                                                    encoded_buf[i + 2],
+                                                                ])
+                                                                .await;
+                                                            i += 3;
+                                                            packet_count += 1;
+                                                        } else {
+                                                            let packet = if remaining == 2 {
+                                                                [
+                                                                    0x07,
+                                                                    encoded_buf[i],
+                                                                    encoded_buf[i + 1],
+                                                                    0xF7,
+                                                                ]
+                                                            } else if remaining == 1 {
+                                                                [0x06, encoded_buf[i], 0xF7, 0x00]
                                                             } else {
-                                                                let packet = if remaining == 2 {
-                                                                    [
-                                                                        0x07,
-                                                                        encoded_buf[i],
-                                                                        encoded_buf[i + 1],
-                                                                        0xF7,
-                                                                    ]
-                                                                } else if remaining == 1 {
-                                                                    [
-                                                                        0x06,
-                                                                        encoded_buf[i],
-                                                                        0xF7,
-                                                                        0x00,
-                                                                    ]
-                                                                } else {
-                                                                    [0x05, 0xF7, 0x00, 0x00]
-                                                                };
-
-                                                                let _ = sender
-                                                                    .write_packet(&packet)
-                                                                    .await;
-                                                                packet_count += 1;
-                                                                f7_sent = true;
-                                                                break;
-                                                            }
-                                                        }
+                                                                [0x05, 0xF7, 0x00, 0x00]
+                                                            };
 
-                                                        if !f7_sent {
-                                                            let p_end = [0x05, 0xF7, 0x00, 0x00];
                                                             let _ =
-                                                                sender.write_packet(&p_end).await;
+                                                                sender.write_packet(&packet).await;
                                                             packet_count += 1;
+                                                            f7_sent = true;
+                                                            break;
                                                         }
-                                                        log_midi!(
-                                                            "SysEx: Dump Sent ({} packets)\r\n",
-                                                            packet_count
-                                                        );
                                                     }
-                                                    CMD_WRITE_REQ => {
-                                                        log_midi!(
-                                                            "SysEx: Write Request ({} bytes)\r\n",
-                                                            msg.len()
-                                                        );
-                                                        let encoded_data = &msg[4..msg.len() - 1];
-                                                        if encoded_data.len() == 8192 {
-                                                            let mut decoded_data = vec![0u8; 4096];
-                                                            for i in 0..4096 {
-                                                                let h = encoded_data[i * 2];
-                                                                let l = encoded_data[i * 2 + 1];
-                                                                decoded_data[i] =
-                                                                    (h << 4) | (l & 0x0F);
-                                                            }
 
-                                                            let magic = u32::from_le_bytes([
-                                                                decoded_data[0],
-                                                                decoded_data[1],
-                                                                decoded_data[2],
-                                                                decoded_data[3],
-                                                            ]);
-                                                            let version = u32::from_le_bytes([
-                                                                decoded_data[4],
-                                                                decoded_data[5],
-                                                                decoded_data[6],
-                                                                decoded_data[7],
-                                                            ]);
-
-                                                            if magic == STORAGE_MAGIC
-                                                                && version == STORAGE_VERSION
+                                                    if !f7_sent {
+                                                        let p_end = [0x05, 0xF7, 0x00, 0x00];
+                                                        let _ = sender.write_packet(&p_end).await;
+                                                        packet_count += 1;
+                                                    }
+                                                    log_midi!(
+                                                        "SysEx: Dump Sent ({} packets)\r\n",
+                                                        packet_count
+                                                    );
+                                                }
+                                                CMD_WRITE_REQ => {
+                                                    log_midi!(
+                                                        "SysEx: Write Request ({} bytes)\r\n",
+                                                        msg.len()
+                                                    );
+                                                    let encoded_data = &msg[4..msg.len() - 1];
+                                                    if encoded_data.len() == 8192 {
+                                                        let mut decoded_data = vec![0u8; 4096];
+                                                        for i in 0..4096 {
+                                                            let h = encoded_data[i * 2];
+                                                            let l = encoded_data[i * 2 + 1];
+                                                            decoded_data[i] = (h << 4) | (l & 0x0F);
+                                                        }
+
+                                                        let magic = u32::from_le_bytes([
+                                                            decoded_data[0],
+                                                            decoded_data[1],
+                                                            decoded_data[2],
+                                                            decoded_data[3],
+                                                        ]);
+                                                        let version = u32::from_le_bytes([
+                                                            decoded_data[4],
+                                                            decoded_data[5],
+                                                            decoded_data[6],
+                                                            decoded_data[7],
+                                                        ]);
+
+                                                        if magic == STORAGE_MAGIC
+                                                            && version == STORAGE_VERSION
+                                                        {
+                                                            storage.write_raw(&decoded_data).await;
+                                                            log_midi!("SysEx: Write Success\r\n");
+
+                                                            let _ = sender
+                                                                .write_packet(&[
+                                                                    0x04,
+                                                                    SYSEX_START,
+                                                                    SYSEX_ID,
+                                                                    SYSEX_MODEL,
+                                                                ])
+                                                                .await;
+                                                            let _ = sender
+                                                                .write_packet(&[
+                                                                    0x06,
+                                                                    CMD_WRITE_SUCCESS,
+                                                                    SYSEX_END,
+                                                                    0x00,
+                                                                ])
+                                                                .await;
+
+                                                            if let Some(preset) = storage
+                                                                .load_preset(current_preset_index)
+                                                                .await
                                                             {
-                                                                storage
-                                                                    .write_raw(&decoded_data)
-                                                                    .await;
-                                                                log_midi!(
-                                                                    "SysEx: Write Success\r\n"
+                                                                log_midi!("Reloading active preset {}\r\n", current_preset_index);
+                                                                load_preset(
+                                                                    preset,
+                                                                    &mut patch,
+                                                                    &mut notes,
+                                                                    &mut voices,
+                                                                    &midi_control,
+                                                                    &mut events,
                                                                 );
-
-                                                                let _ = sender
-                                                                    .write_packet(&[
-                                                                        0x04,
-                                                                        SYSEX_START,
-                                                                        SYSEX_ID,
-                                                                        SYSEX_MODEL,
-                                                                    ])
-                                                                    .await;
-                                                                let _ = sender
-                                                                    .write_packet(&[
-                                                                        0x06,
-                                                                        CMD_WRITE_SUCCESS,
-                                                                        SYSEX_END,
-                                                                        0x00,
-                                                                    ])
-                                                                    .await;
-
-                                                                if let Some(preset) = storage
-                                                                    .load_preset(
-                                                                        current_preset_index,
-                                                                    )
-                                                                    .await
-                                                                {
-                                                                    log_midi!("Reloading active preset {}\r\n", current_preset_index);
-                                                                    let cutoff_norm = libm::log10f(
-                                                                        preset.filter.cutoff / 20.0,
-                                                                    )
-                                                                        / libm::log10f(1000.0);
-                                                                    midi_control.set_parameter_1(
-                                                                        cutoff_norm.clamp(0.0, 1.0),
-                                                                    );
-                                                                    let res_norm =
-                                                                        (preset.filter.resonance
-                                                                            - 0.707)
-                                                                            / 9.3;
-                                                                    midi_control.set_parameter_2(
-                                                                        res_norm.clamp(0.0, 1.0),
-                                                                    );
-                                                                    midi_control.set_portamento(
-                                                                        preset.portamento,
-                                                                    );
-
-                                                                    let _ = PRESET_CHANNEL
-                                                                        .try_send(preset);
-                                                                }
-                                                            } else {
-                                                                log_midi!("SysEx: Invalid Magic/Version ({:X}, {})\r\n", magic, version);
-                                                                let _ = sender
-                                                                    .write_packet(&[
-                                                                        0x04,
-                                                                        SYSEX_START,
-                                                                        SYSEX_ID,
-                                                                        SYSEX_MODEL,
-                                                                    ])
-                                                                    .await;
-                                                                let _ = sender
-                                                                    .write_packet(&[
-                                                                        0x07,
-                                                                        CMD_WRITE_ERROR,
-                                                                        ERR_BAD_MAGIC,
-                                                                        SYSEX_END,
-                                                                    ])
-                                                                    .await;
                                                             }
                                                         } else {
-                                                            log_midi!(
-                                                                "SysEx: Invalid Length ({})\r\n",
-                                                                encoded_data.len()
-                                                            );
+                                                            log_midi!("SysEx: Invalid Magic/Version ({:X}, {})\r\n", magic, version);
                                                             let _ = sender
                                                                 .write_packet(&[
                                                                     0x04,
@@ -623,21 +1309,166 @@ pub async fn midi_task(
                                                                 .write_packet(&[
                                                                     0x07,
                                                                     CMD_WRITE_ERROR,
-                                                                    ERR_BAD_LENGTH,
+                                                                    ERR_BAD_MAGIC,
                                                                     SYSEX_END,
                                                                 ])
                                                                 .await;
                                                         }
+                                                    } else {
+                                                        log_midi!(
+                                                            "SysEx: Invalid Length ({})\r\n",
+                                                            encoded_data.len()
+                                                        );
+                                                        let _ = sender
+                                                            .write_packet(&[
+                                                                0x04,
+                                                                SYSEX_START,
+                                                                SYSEX_ID,
+                                                                SYSEX_MODEL,
+                                                            ])
+                                                            .await;
+                                                        let _ = sender
+                                                            .write_packet(&[
+                                                                0x07,
+                                                                CMD_WRITE_ERROR,
+                                                                ERR_BAD_LENGTH,
+                                                                SYSEX_END,
+                                                            ])
+                                                            .await;
+                                                    }
+                                                }
+                                                CMD_SET_PARAM if msg.len() >= 8 => {
+                                                    if let Some(id) =
+                                                        ParamId::from_index(msg[4] as usize)
+                                                    {
+                                                        let value = id.def().denormalize(
+                                                            decode_14bit(msg[5], msg[6]),
+                                                        );
+                                                        set_parameter(
+                                                            id,
+                                                            value,
+                                                            &mut patch,
+                                                            &mut notes,
+                                                            &mut voices,
+                                                            &midi_control,
+                                                            &mut events,
+                                                        );
                                                     }
-                                                    _ => {}
                                                 }
+                                                CMD_GET_PARAM if msg.len() >= 6 => {
+                                                    if let Some(id) =
+                                                        ParamId::from_index(msg[4] as usize)
+                                                    {
+                                                        let def = id.def();
+                                                        let (msb, lsb) = encode_14bit(
+                                                            def.normalize(def.get(&patch.preset)),
+                                                        );
+                                                        send_sysex(
+                                                            &mut sender,
+                                                            &[CMD_PARAM_VALUE, id as u8, msb, lsb],
+                                                        )
+                                                        .await;
+                                                    }
+                                                }
+                                                CMD_LEARN if msg.len() >= 6 => {
+                                                    learning = ParamId::from_index(msg[4] as usize);
+                                                    match learning {
+                                                        Some(id) => log_midi!(
+                                                            "SysEx: Learn {}\r\n",
+                                                            id.def().name
+                                                        ),
+                                                        None => {
+                                                            log_midi!("SysEx: Learn cancelled\r\n")
+                                                        }
+                                                    }
+                                                }
+                                                CMD_SET_CC if msg.len() >= 7 => {
+                                                    if let Some(id) =
+                                                        ParamId::from_index(msg[4] as usize)
+                                                    {
+                                                        let cc = match msg[5] {
+                                                            SYSEX_NONE => None,
+                                                            cc => Some(cc),
+                                                        };
+                                                        assign_cc(
+                                                            id,
+                                                            cc,
+                                                            &mut settings,
+                                                            &mut storage,
+                                                        )
+                                                        .await;
+                                                    }
+                                                }
+                                                CMD_SET_CHANNEL if msg.len() >= 6 => {
+                                                    set_channel(
+                                                        msg[4],
+                                                        &mut settings,
+                                                        &mut storage,
+                                                    )
+                                                    .await;
+                                                }
+                                                CMD_SET_TUNE if msg.len() >= 7 => {
+                                                    let tenths =
+                                                        ((msg[4] as u16) << 7) | msg[5] as u16;
+                                                    set_master_tune(
+                                                        tenths as f32 / 10.0,
+                                                        &mut settings,
+                                                        &mut storage,
+                                                        &midi_control,
+                                                    )
+                                                    .await;
+                                                }
+                                                CMD_SAVE_PRESET => {
+                                                    log_midi!(
+                                                        "SysEx: Save Preset {}\r\n",
+                                                        current_preset_index
+                                                    );
+                                                    if storage
+                                                        .save_preset(
+                                                            current_preset_index,
+                                                            &patch.preset,
+                                                        )
+                                                        .await
+                                                    {
+                                                        send_sysex(
+                                                            &mut sender,
+                                                            &[CMD_WRITE_SUCCESS],
+                                                        )
+                                                        .await;
+                                                    } else {
+                                                        send_sysex(
+                                                            &mut sender,
+                                                            &[CMD_WRITE_ERROR, ERR_BAD_INDEX],
+                                                        )
+                                                        .await;
+                                                    }
+                                                }
+                                                _ => {}
                                             }
+                                        } else if msg.len() >= 8
+                                            && msg[0] == SYSEX_START
+                                            && msg[msg.len() - 1] == SYSEX_END
+                                            && (msg[1] == MTS_NON_REALTIME
+                                                || msg[1] == MTS_REALTIME)
+                                            && msg[3] == MTS_SUB_ID
+                                        {
+                                            receive_tuning(
+                                                msg,
+                                                &settings,
+                                                &mut storage,
+                                                &midi_control,
+                                            )
+                                            .await;
                                         }
                                     }
                                 }
                                 continue;
                             }
 
+                            if !settings.accepts(status) {
+                                continue;
+                            }
+
                             log_midi!(
                                 "MIDI: [{:02X}-{:02X}-{:02X}-{:02X}] - ",
                                 cin,
@@ -650,37 +1481,35 @@ pub async fn midi_task(
 
                             match cmd {
                                 NOTE_ON if d2 > 0 => {
-                                    let freq = midi_to_freq(d1);
+                                    let freq = midi_control.note_freq(d1);
                                     log_midi!("NOTE ON: {} ({} Hz)", d1, freq);
-                                    notes.note_on(d1);
-                                    midi_control.set_freq(freq);
-                                    midi_control.set_gate(true);
-                                    let _ = LED_SIGNAL_CHANNEL.try_send(true);
+                                    note_on(
+                                        d1,
+                                        d2,
+                                        &mut notes,
+                                        &mut voices,
+                                        &midi_control,
+                                        &mut events,
+                                    );
                                 }
                                 NOTE_OFF | NOTE_ON => {
-                                    let freq = midi_to_freq(d1);
+                                    let freq = midi_control.note_freq(d1);
                                     log_midi!("NOTE OFF: {}", freq);
-                                    notes.note_off(d1);
-
-                                    if let Some(last_note) = notes.active_note() {
-                                        midi_control.set_freq(midi_to_freq(last_note));
-                                        midi_control.set_gate(true);
-                                    } else {
-                                        midi_control.set_gate(false);
-                                        let _ = LED_SIGNAL_CHANNEL.try_send(false);
-                                    }
+                                    note_off(
+                                        d1,
+                                        &mut notes,
+                                        &mut voices,
+                                        &midi_control,
+                                        &mut events,
+                                    );
                                 }
                                 CONTROL_CHANGE => {
                                     let val_norm = d2 as f32 / 127.0;
                                     match d1 {
                                         CC_MOD_WHEEL => {
+                                            cc_msb[d1 as usize] = d2;
                                             log_midi!("MOD WHEEL: {:.2}", val_norm);
-                                            midi_control.set_mod_wheel(val_norm);
-                                        }
-                                        CC_PORTAMENTO_TIME => {
-                                            let amount = val_norm;
-                                            log_midi!("PORTAMENTO: {:.2}", amount);
-                                            midi_control.set_portamento(amount);
+                                            events.send(MidiEvent::ModWheel(val_norm));
                                         }
                                         CC_SUSTAIN => {
                                             let sustain_on = d2 >= 64;
@@ -688,33 +1517,127 @@ pub async fn midi_task(
                                                 "SUSTAIN: {}",
                                                 if sustain_on { "ON" } else { "OFF" }
                                             );
-                                            notes.set_sustain(sustain_on);
-
-                                            if !sustain_on {
-                                                if let Some(last_note) = notes.active_note() {
-                                                    midi_control.set_freq(midi_to_freq(last_note));
-                                                    midi_control.set_gate(true);
-                                                } else {
-                                                    midi_control.set_gate(false);
-                                                    let _ = LED_SIGNAL_CHANNEL.try_send(false);
-                                                }
-                                            }
+                                            set_sustain(
+                                                sustain_on,
+                                                &mut notes,
+                                                &mut voices,
+                                                &midi_control,
+                                                &mut events,
+                                            );
+                                        }
+                                        CC_RPN_MSB => {
+                                            rpn.0 = d2;
+                                            nrpn = RPN_NULL;
+                                        }
+                                        CC_RPN_LSB => {
+                                            rpn.1 = d2;
+                                            nrpn = RPN_NULL;
                                         }
-                                        CC_FILTER_RESONANCE => {
-                                            log_midi!("RESONANCE: {:.2}", val_norm);
-                                            midi_control.set_parameter_2(val_norm);
+                                        CC_NRPN_MSB => {
+                                            nrpn.0 = d2;
+                                            rpn = RPN_NULL;
                                         }
-                                        CC_FILTER_CUTOFF => {
-                                            log_midi!("CUTOFF: {:.2}", val_norm);
-                                            midi_control.set_parameter_1(val_norm);
+                                        CC_NRPN_LSB => {
+                                            nrpn.1 = d2;
+                                            rpn = RPN_NULL;
                                         }
+                                        CC_DATA_ENTRY | CC_DATA_ENTRY_LSB
+                                            if rpn == RPN_BEND_RANGE =>
+                                        {
+                                            let current = patch.preset.bend.up;
+                                            let range = if d1 == CC_DATA_ENTRY {
+                                                d2 as f32
+                                            } else {
+                                                libm::floorf(current) + d2 as f32 / 100.0
+                                            };
+                                            log_midi!("BEND RANGE: {:.2}", range);
+                                            for id in [ParamId::BendUp, ParamId::BendDown] {
+                                                set_parameter(
+                                                    id,
+                                                    range,
+                                                    &mut patch,
+                                                    &mut notes,
+                                                    &mut voices,
+                                                    &midi_control,
+                                                    &mut events,
+                                                );
+                                            }
+                                        }
+                                        CC_DATA_ENTRY | CC_DATA_ENTRY_LSB
+                                            if nrpn.0 == NRPN_PARAM_BANK =>
+                                        {
+                                            let norm = if d1 == CC_DATA_ENTRY {
+                                                data_msb = d2;
+                                                val_norm
+                                            } else {
+                                                decode_14bit(data_msb, d2)
+                                            };
+                                            if let Some(id) = ParamId::from_index(nrpn.1 as usize) {
+                                                set_parameter(
+                                                    id,
+                                                    id.def().denormalize(norm),
+                                                    &mut patch,
+                                                    &mut notes,
+                                                    &mut voices,
+                                                    &midi_control,
+                                                    &mut events,
+                                                );
+                                            }
+                                        }
+                                        CC_DATA_ENTRY | CC_DATA_ENTRY_LSB => {}
                                         CC_ALL_SOUND_OFF | CC_ALL_NOTES_OFF => {
                                             log_midi!("ALL NOTES/SOUND OFF");
                                             notes.clear();
-                                            midi_control.reset();
+                                            voices.clear();
+                                            events.send(MidiEvent::AllNotesOff);
                                             let _ = LED_SIGNAL_CHANNEL.try_send(false);
                                         }
-                                        _ => {}
+                                        _ if learning.is_some() && !is_reserved_cc(d1) => {
+                                            if let Some(id) = learning.take() {
+                                                assign_cc(
+                                                    id,
+                                                    Some(d1),
+                                                    &mut settings,
+                                                    &mut storage,
+                                                )
+                                                .await;
+                                            }
+                                        }
+                                        _ if (CC_LSB_OFFSET..2 * CC_LSB_OFFSET).contains(&d1) => {
+                                            let msb_cc = d1 - CC_LSB_OFFSET;
+                                            let norm = decode_14bit(cc_msb[msb_cc as usize], d2);
+                                            if msb_cc == CC_MOD_WHEEL {
+                                                events.send(MidiEvent::ModWheel(norm));
+                                            } else if let Some(id) =
+                                                settings.cc_map.param_for(msb_cc)
+                                            {
+                                                set_parameter(
+                                                    id,
+                                                    id.def().denormalize(norm),
+                                                    &mut patch,
+                                                    &mut notes,
+                                                    &mut voices,
+                                                    &midi_control,
+                                                    &mut events,
+                                                );
+                                            }
+                                        }
+                                        _ => {
+                                            if d1 < CC_LSB_OFFSET {
+                                                cc_msb[d1 as usize] = d2;
+                                            }
+                                            if let Some(id) = settings.cc_map.param_for(d1) {
+                                                set_parameter(
+                                                    id,
+                                                    id.def().denormalize(val_norm),
+                                                    &mut patch,
+                                                    &mut notes,
+                                                    &mut voices,
+                                                    &midi_control,
+                                                    &mut events,
+                                                );
+                                            }
+                                        }
                                     }
                                 }
                                 PROGRAM_CHANGE => {
@@ -722,23 +1645,46 @@ pub async fn midi_task(
                                     current_preset_index = d1 as usize;
                                     if let Some(preset) = storage.load_preset(d1 as usize).await {
                                         log_midi!("Loaded: {}\r\n", preset.get_name());
-                                        let cutoff_norm = libm::log10f(preset.filter.cutoff / 20.0)
-                                            / libm::log10f(1000.0);
-                                        midi_control.set_parameter_1(cutoff_norm.clamp(0.0, 1.0));
-                                        let res_norm = (preset.filter.resonance - 0.707) / 9.3;
-                                        midi_control.set_parameter_2(res_norm.clamp(0.0, 1.0));
-                                        midi_control.set_portamento(preset.portamento);
-                                        let _ = PRESET_CHANNEL.try_send(preset);
+                                        load_preset(
+                                            preset,
+                                            &mut patch,
+                                            &mut notes,
+                                            &mut voices,
+                                            &midi_control,
+                                            &mut events,
+                                        );
                                     } else {
                                         log_midi!("Preset {} not found\r\n", d1);
                                     }
                                 }
+                                CHANNEL_PRESSURE => {
+                                    let value = d1 as f32 / 127.0;
+                                    log_midi!("AFTERTOUCH: {:.2}", value);
+                                    events.send(MidiEvent::ChannelPressure(value));
+                                }
+                                POLY_PRESSURE => {
+                                    let value = d2 as f32 / 127.0;
+                                    log_midi!("POLY AFTERTOUCH: {} {:.2}", d1, value);
+                                    let voice_settings = midi_control.get_voice_settings();
+                                    let voice = match voice_settings.get_mode() {
+                                        VoiceMode::Mono => notes
+                                            .active_note(voice_settings.get_priority())
+                                            .filter(|&n| n == d1)
+                                            .map(|_| 0),
+                                        VoiceMode::Poly => voices.voice_for(d1),
+                                    };
+                                    if let Some(voice) = voice {
+                                        events.send(MidiEvent::PolyPressure {
+                                            voice: voice as u8,
+                                            value,
+                                        });
+                                    }
+                                }
                                 PITCH_BEND => {
                                     let val = ((d2 as u16) << 7) | (d1 as u16);
                                     log_midi!("PITCHBEND: {}", val);
                                     let norm = (val as f32 - 8192.0) / 8192.0;
-                                    let factor = libm::powf(2.0, (norm * 2.0) / 12.0);
-                                    midi_control.set_pitch_bend(factor);
+                                    events.send(MidiEvent::PitchBend(norm));
                                 }
                                 _ => {}
                             }
@@ -752,8 +1698,62 @@ pub async fn midi_task(
                     SystemCommand::ResetStorage => {
                         log_midi!("Command: Reset Storage...\r\n");
                         storage.format().await;
+                        settings = GlobalSettings::defaults();
+                        storage.save_settings(&settings).await;
+                        storage.format_tunings().await;
+                        midi_control.set_master_tune(settings.master_tune);
+                        load_tunings(&mut storage, &midi_control).await;
                         log_midi!("Storage Reset Complete.\r\n");
                     }
+                    SystemCommand::SetParameter(id, value) => {
+                        set_parameter(
+                            id,
+                            value,
+                            &mut patch,
+                            &mut notes,
+                            &mut voices,
+                            &midi_control,
+                            &mut events,
+                        );
+                    }
+                    SystemCommand::GetParameter(id) => {
+                        let def = id.def();
+                        log_midi!(
+                            "{} = {:.3}{}\r\n",
+                            def.name,
+                            def.get(&patch.preset),
+                            def.unit
+                        );
+                    }
+                    SystemCommand::ListParameters => {
+                        list_parameters(&patch.preset, &settings.cc_map).await;
+                    }
+                    SystemCommand::Learn(id) => {
+                        learning = id;
+                        match id {
+                            Some(id) => log_midi!("Learn {}: move a controller\r\n", id.def().name),
+                            None => log_midi!("Learn cancelled\r\n"),
+                        }
+                    }
+                    SystemCommand::SetCc(id, cc) => {
+                        assign_cc(id, cc, &mut settings, &mut storage).await;
+                    }
+                    SystemCommand::SetChannel(channel) => {
+                        set_channel(channel, &mut settings, &mut storage).await;
+                    }
+                    SystemCommand::SetMasterTune(a4) => {
+                        set_master_tune(a4, &mut settings, &mut storage, &midi_control).await;
+                    }
+                    SystemCommand::SetFxTails(enabled) => {
+                        log_midi!("FX tails {}\r\n", if enabled { "on" } else { "off" });
+                        midi_control.set_fx_tails(enabled);
+                    }
+                    SystemCommand::SavePreset => {
+                        log_midi!("Command: Save Preset {}\r\n", current_preset_index);
+                        storage
+                            .save_preset(current_preset_index, &patch.preset)
+                            .await;
+                    }
                 },
             }
         }
@@ -1 +1,4 @@
+pub mod events;
 pub mod midi;
+pub mod params;
+pub mod voices;
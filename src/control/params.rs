@@ -0,0 +1,1146 @@
+use core::mem::offset_of;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use crate::data::presets::Preset;
+use crate::data::tuning::TUNING_SLOTS;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Curve {
+    Linear,
+    Exponential,
+    Stepped,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ParamKind {
+    Float,
+    Int,
+}
+
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq)]
+pub enum ParamId {
+    Osc1Waveform,
+    Osc1Level,
+    Osc1Octave,
+    Osc1Detune,
+    Osc1Vibrato,
+    Osc2Waveform,
+    Osc2Level,
+    Osc2Octave,
+    Osc2Detune,
+    Osc2Vibrato,
+    Osc3Waveform,
+    Osc3Level,
+    Osc3Octave,
+    Osc3Detune,
+    Osc3Vibrato,
+    NoiseLevel,
+    Portamento,
+    FilterCutoff,
+    FilterResonance,
+    FilterEnvAmount,
+    FilterAttack,
+    FilterDecay,
+    FilterSustain,
+    FilterRelease,
+    AmpAttack,
+    AmpDecay,
+    AmpSustain,
+    AmpRelease,
+    LfoEnabled,
+    LfoRate,
+    LfoWaveform,
+    LfoVibrato,
+    LfoFilter,
+    DelayTime,
+    DelayFeedback,
+    DelayMix,
+    DelayEnabled,
+    ReverbSize,
+    ReverbDamping,
+    ReverbMix,
+    ReverbEnabled,
+    VoiceMode,
+    VoiceCount,
+    VoiceSteal,
+    VelocityAmp,
+    VelocityFilter,
+    AftertouchVibrato,
+    AftertouchCutoff,
+    AftertouchAmp,
+    WheelVibrato,
+    WheelFilter,
+    BendUp,
+    BendDown,
+    BendCutoff,
+    VoicePriority,
+    VoiceTrigger,
+    PortamentoMode,
+    FilterKeytrack,
+    Osc3Lfo,
+    Osc3ModPitch,
+    Osc3ModCutoff,
+    Osc1PulseWidth,
+    Osc2PulseWidth,
+    Osc3PulseWidth,
+    PwmLfo,
+    PwmEnv,
+    OscSync,
+    FmAmount,
+    FmMode,
+    Osc1Semitone,
+    Osc2Semitone,
+    Osc3Semitone,
+    Tuning,
+    NoiseColor,
+    ExternalLevel,
+}
+
+pub struct ParamDef {
+    pub id: ParamId,
+    pub name: &'static str,
+    pub unit: &'static str,
+    pub min: f32,
+    pub max: f32,
+    pub curve: Curve,
+    pub kind: ParamKind,
+    pub default: f32,
+    pub cc: u8,
+    pub offset: usize,
+}
+
+macro_rules! param {
+    ($id:ident, $name:expr, $unit:expr, $min:expr, $max:expr, $curve:ident, $kind:ident, $default:expr, $cc:expr, $($field:ident).+) => {
+        ParamDef {
+            id: ParamId::$id,
+            name: $name,
+            unit: $unit,
+            min: $min,
+            max: $max,
+            curve: Curve::$curve,
+            kind: ParamKind::$kind,
+            default: $default,
+            cc: $cc,
+            offset: offset_of!(Preset, $($field).+),
+        }
+    };
+}
+
+pub const PARAM_COUNT: usize = ParamId::ExternalLevel as usize + 1;
+pub const MAX_PARAMS: usize = 128;
+const NO_CC: u8 = 0xFF;
+
+pub static PARAMS: [ParamDef; PARAM_COUNT] = [
+    param!(
+        Osc1Waveform,
+        "osc1.waveform",
+        "",
+        0.0,
+        6.0,
+        Stepped,
+        Int,
+        2.0,
+        14,
+        osc1.waveform
+    ),
+    param!(
+        Osc1Level,
+        "osc1.level",
+        "",
+        0.0,
+        1.0,
+        Linear,
+        Float,
+        1.0,
+        15,
+        osc1.level
+    ),
+    param!(
+        Osc1Octave,
+        "osc1.octave",
+        "oct",
+        -4.0,
+        4.0,
+        Stepped,
+        Float,
+        0.0,
+        16,
+        osc1.octave
+    ),
+    param!(
+        Osc1Detune,
+        "osc1.detune",
+        "cents",
+        -50.0,
+        50.0,
+        Linear,
+        Float,
+        0.0,
+        17,
+        osc1.detune
+    ),
+    param!(
+        Osc1Vibrato,
+        "osc1.vibrato",
+        "",
+        0.0,
+        1.0,
+        Stepped,
+        Int,
+        1.0,
+        18,
+        osc1.enable_vibrato
+    ),
+    param!(
+        Osc2Waveform,
+        "osc2.waveform",
+        "",
+        0.0,
+        6.0,
+        Stepped,
+        Int,
+        2.0,
+        19,
+        osc2.waveform
+    ),
+    param!(
+        Osc2Level,
+        "osc2.level",
+        "",
+        0.0,
+        1.0,
+        Linear,
+        Float,
+        0.0,
+        20,
+        osc2.level
+    ),
+    param!(
+        Osc2Octave,
+        "osc2.octave",
+        "oct",
+        -4.0,
+        4.0,
+        Stepped,
+        Float,
+        0.0,
+        21,
+        osc2.octave
+    ),
+    param!(
+        Osc2Detune,
+        "osc2.detune",
+        "cents",
+        -50.0,
+        50.0,
+        Linear,
+        Float,
+        0.0,
+        22,
+        osc2.detune
+    ),
+    param!(
+        Osc2Vibrato,
+        "osc2.vibrato",
+        "",
+        0.0,
+        1.0,
+        Stepped,
+        Int,
+        1.0,
+        23,
+        osc2.enable_vibrato
+    ),
+    param!(
+        Osc3Waveform,
+        "osc3.waveform",
+        "",
+        0.0,
+        6.0,
+        Stepped,
+        Int,
+        2.0,
+        24,
+        osc3.waveform
+    ),
+    param!(
+        Osc3Level,
+        "osc3.level",
+        "",
+        0.0,
+        1.0,
+        Linear,
+        Float,
+        0.0,
+        25,
+        osc3.level
+    ),
+    param!(
+        Osc3Octave,
+        "osc3.octave",
+        "oct",
+        -4.0,
+        4.0,
+        Stepped,
+        Float,
+        0.0,
+        26,
+        osc3.octave
+    ),
+    param!(
+        Osc3Detune,
+        "osc3.detune",
+        "cents",
+        -50.0,
+        50.0,
+        Linear,
+        Float,
+        0.0,
+        27,
+        osc3.detune
+    ),
+    param!(
+        Osc3Vibrato,
+        "osc3.vibrato",
+        "",
+        0.0,
+        1.0,
+        Stepped,
+        Int,
+        1.0,
+        28,
+        osc3.enable_vibrato
+    ),
+    param!(
+        NoiseLevel,
+        "noise.level",
+        "",
+        0.0,
+        1.0,
+        Linear,
+        Float,
+        0.0,
+        29,
+        noise_level
+    ),
+    param!(
+        Portamento,
+        "portamento",
+        "s",
+        0.0,
+        5.0,
+        Linear,
+        Float,
+        0.0,
+        5,
+        portamento
+    ),
+    param!(
+        FilterCutoff,
+        "filter.cutoff",
+        "Hz",
+        20.0,
+        20000.0,
+        Exponential,
+        Float,
+        20000.0,
+        74,
+        filter.cutoff
+    ),
+    param!(
+        FilterResonance,
+        "filter.resonance",
+        "",
+        0.707,
+        10.0,
+        Linear,
+        Float,
+        0.707,
+        71,
+        filter.resonance
+    ),
+    param!(
+        FilterEnvAmount,
+        "filter.env_amount",
+        "Hz",
+        0.0,
+        10000.0,
+        Linear,
+        Float,
+        0.0,
+        79,
+        filter.env_amount
+    ),
+    param!(
+        FilterAttack,
+        "filter.attack",
+        "s",
+        0.001,
+        10.0,
+        Exponential,
+        Float,
+        0.001,
+        85,
+        filter.attack
+    ),
+    param!(
+        FilterDecay,
+        "filter.decay",
+        "s",
+        0.001,
+        10.0,
+        Exponential,
+        Float,
+        0.001,
+        86,
+        filter.decay
+    ),
+    param!(
+        FilterSustain,
+        "filter.sustain",
+        "",
+        0.0,
+        1.0,
+        Linear,
+        Float,
+        1.0,
+        87,
+        filter.sustain
+    ),
+    param!(
+        FilterRelease,
+        "filter.release",
+        "s",
+        0.001,
+        10.0,
+        Exponential,
+        Float,
+        0.001,
+        88,
+        filter.release
+    ),
+    param!(
+        AmpAttack,
+        "amp.attack",
+        "s",
+        0.001,
+        10.0,
+        Exponential,
+        Float,
+        0.01,
+        73,
+        amp.attack
+    ),
+    param!(
+        AmpDecay,
+        "amp.decay",
+        "s",
+        0.001,
+        10.0,
+        Exponential,
+        Float,
+        0.1,
+        75,
+        amp.decay
+    ),
+    param!(
+        AmpSustain,
+        "amp.sustain",
+        "",
+        0.0,
+        1.0,
+        Linear,
+        Float,
+        1.0,
+        90,
+        amp.sustain
+    ),
+    param!(
+        AmpRelease,
+        "amp.release",
+        "s",
+        0.001,
+        10.0,
+        Exponential,
+        Float,
+        0.1,
+        72,
+        amp.release
+    ),
+    param!(
+        LfoEnabled,
+        "lfo.enabled",
+        "",
+        0.0,
+        1.0,
+        Stepped,
+        Int,
+        0.0,
+        102,
+        lfo_enabled
+    ),
+    param!(
+        LfoRate,
+        "lfo.rate",
+        "Hz",
+        0.05,
+        20.0,
+        Exponential,
+        Float,
+        1.0,
+        76,
+        lfo.frequency
+    ),
+    param!(
+        LfoWaveform,
+        "lfo.waveform",
+        "",
+        0.0,
+        4.0,
+        Stepped,
+        Int,
+        0.0,
+        103,
+        lfo.waveform
+    ),
+    param!(
+        LfoVibrato,
+        "lfo.vibrato",
+        "Hz",
+        0.0,
+        20.0,
+        Linear,
+        Float,
+        0.0,
+        77,
+        lfo.vibrato_amount
+    ),
+    param!(
+        LfoFilter,
+        "lfo.filter",
+        "Hz",
+        0.0,
+        5000.0,
+        Linear,
+        Float,
+        0.0,
+        104,
+        lfo.filter_amount
+    ),
+    param!(
+        DelayTime,
+        "delay.time",
+        "s",
+        0.01,
+        0.26,
+        Linear,
+        Float,
+        0.25,
+        105,
+        delay.time
+    ),
+    param!(
+        DelayFeedback,
+        "delay.feedback",
+        "",
+        0.0,
+        0.95,
+        Linear,
+        Float,
+        0.3,
+        106,
+        delay.feedback
+    ),
+    param!(
+        DelayMix,
+        "delay.mix",
+        "",
+        0.0,
+        1.0,
+        Linear,
+        Float,
+        0.3,
+        107,
+        delay.mix
+    ),
+    param!(
+        DelayEnabled,
+        "delay.enabled",
+        "",
+        0.0,
+        1.0,
+        Stepped,
+        Int,
+        0.0,
+        108,
+        delay.enabled
+    ),
+    param!(
+        ReverbSize,
+        "reverb.size",
+        "",
+        0.0,
+        1.0,
+        Linear,
+        Float,
+        0.5,
+        109,
+        reverb.size
+    ),
+    param!(
+        ReverbDamping,
+        "reverb.damping",
+        "",
+        0.0,
+        1.0,
+        Linear,
+        Float,
+        0.5,
+        110,
+        reverb.damping
+    ),
+    param!(
+        ReverbMix,
+        "reverb.mix",
+        "",
+        0.0,
+        1.0,
+        Linear,
+        Float,
+        0.1,
+        91,
+        reverb.mix
+    ),
+    param!(
+        ReverbEnabled,
+        "reverb.enabled",
+        "",
+        0.0,
+        1.0,
+        Stepped,
+        Int,
+        0.0,
+        111,
+        reverb.enabled
+    ),
+    param!(
+        VoiceMode,
+        "voice.mode",
+        "",
+        0.0,
+        1.0,
+        Stepped,
+        Int,
+        0.0,
+        112,
+        voice.mode
+    ),
+    param!(
+        VoiceCount,
+        "voice.count",
+        "",
+        1.0,
+        8.0,
+        Stepped,
+        Int,
+        1.0,
+        113,
+        voice.count
+    ),
+    param!(
+        VoiceSteal,
+        "voice.steal",
+        "",
+        0.0,
+        1.0,
+        Stepped,
+        Int,
+        0.0,
+        114,
+        voice.steal
+    ),
+    param!(
+        VelocityAmp,
+        "velocity.amp",
+        "",
+        0.0,
+        1.0,
+        Linear,
+        Float,
+        0.0,
+        80,
+        velocity.amp
+    ),
+    param!(
+        VelocityFilter,
+        "velocity.filter",
+        "",
+        0.0,
+        1.0,
+        Linear,
+        Float,
+        0.0,
+        81,
+        velocity.filter
+    ),
+    param!(
+        AftertouchVibrato,
+        "aftertouch.vibrato",
+        "Hz",
+        0.0,
+        20.0,
+        Linear,
+        Float,
+        0.0,
+        82,
+        aftertouch.vibrato
+    ),
+    param!(
+        AftertouchCutoff,
+        "aftertouch.cutoff",
+        "Hz",
+        0.0,
+        5000.0,
+        Linear,
+        Float,
+        0.0,
+        83,
+        aftertouch.cutoff
+    ),
+    param!(
+        AftertouchAmp,
+        "aftertouch.amp",
+        "",
+        0.0,
+        1.0,
+        Linear,
+        Float,
+        0.0,
+        89,
+        aftertouch.amp
+    ),
+    param!(
+        WheelVibrato,
+        "wheel.vibrato",
+        "",
+        0.0,
+        1.0,
+        Linear,
+        Float,
+        0.0,
+        92,
+        wheel.vibrato
+    ),
+    param!(
+        WheelFilter,
+        "wheel.filter",
+        "",
+        0.0,
+        1.0,
+        Linear,
+        Float,
+        0.0,
+        93,
+        wheel.filter
+    ),
+    param!(BendUp, "bend.up", "st", 0.0, 24.0, Linear, Float, 2.0, 94, bend.up),
+    param!(
+        BendDown,
+        "bend.down",
+        "st",
+        0.0,
+        24.0,
+        Linear,
+        Float,
+        2.0,
+        95,
+        bend.down
+    ),
+    param!(
+        BendCutoff,
+        "bend.cutoff",
+        "Hz",
+        0.0,
+        5000.0,
+        Linear,
+        Float,
+        0.0,
+        115,
+        bend.cutoff
+    ),
+    param!(
+        VoicePriority,
+        "voice.priority",
+        "",
+        0.0,
+        2.0,
+        Stepped,
+        Int,
+        0.0,
+        116,
+        voice.priority
+    ),
+    param!(
+        VoiceTrigger,
+        "voice.trigger",
+        "",
+        0.0,
+        2.0,
+        Stepped,
+        Int,
+        0.0,
+        117,
+        voice.trigger
+    ),
+    param!(
+        PortamentoMode,
+        "portamento.mode",
+        "",
+        0.0,
+        1.0,
+        Stepped,
+        Int,
+        0.0,
+        118,
+        portamento_mode
+    ),
+    param!(
+        FilterKeytrack,
+        "filter.keytrack",
+        "",
+        0.0,
+        3.0,
+        Stepped,
+        Int,
+        0.0,
+        119,
+        filter.keytrack
+    ),
+    param!(
+        Osc3Lfo,
+        "osc3.lfo",
+        "",
+        0.0,
+        1.0,
+        Stepped,
+        Int,
+        0.0,
+        9,
+        osc3.lfo_mode
+    ),
+    param!(
+        Osc3ModPitch,
+        "osc3_mod.pitch",
+        "Hz",
+        0.0,
+        20.0,
+        Linear,
+        Float,
+        0.0,
+        12,
+        osc3_mod.pitch
+    ),
+    param!(
+        Osc3ModCutoff,
+        "osc3_mod.cutoff",
+        "Hz",
+        0.0,
+        5000.0,
+        Linear,
+        Float,
+        0.0,
+        13,
+        osc3_mod.cutoff
+    ),
+    param!(
+        Osc1PulseWidth,
+        "osc1.pulse_width",
+        "",
+        0.05,
+        0.5,
+        Linear,
+        Float,
+        0.5,
+        2,
+        osc1.pulse_width
+    ),
+    param!(
+        Osc2PulseWidth,
+        "osc2.pulse_width",
+        "",
+        0.05,
+        0.5,
+        Linear,
+        Float,
+        0.5,
+        3,
+        osc2.pulse_width
+    ),
+    param!(
+        Osc3PulseWidth,
+        "osc3.pulse_width",
+        "",
+        0.05,
+        0.5,
+        Linear,
+        Float,
+        0.5,
+        4,
+        osc3.pulse_width
+    ),
+    param!(PwmLfo, "pwm.lfo", "", 0.0, 0.45, Linear, Float, 0.0, 30, pwm.lfo),
+    param!(PwmEnv, "pwm.env", "", 0.0, 0.45, Linear, Float, 0.0, 31, pwm.env),
+    param!(
+        OscSync,
+        "cross_mod.sync",
+        "",
+        0.0,
+        1.0,
+        Stepped,
+        Int,
+        0.0,
+        78,
+        cross_mod.sync
+    ),
+    param!(
+        FmAmount,
+        "cross_mod.fm",
+        "",
+        0.0,
+        1.0,
+        Linear,
+        Float,
+        0.0,
+        70,
+        cross_mod.fm
+    ),
+    param!(
+        FmMode,
+        "cross_mod.fm_mode",
+        "",
+        0.0,
+        1.0,
+        Stepped,
+        Int,
+        0.0,
+        NO_CC,
+        cross_mod.fm_mode
+    ),
+    param!(
+        Osc1Semitone,
+        "osc1.semitone",
+        "st",
+        -12.0,
+        12.0,
+        Stepped,
+        Float,
+        0.0,
+        NO_CC,
+        osc1.semitone
+    ),
+    param!(
+        Osc2Semitone,
+        "osc2.semitone",
+        "st",
+        -12.0,
+        12.0,
+        Stepped,
+        Float,
+        0.0,
+        NO_CC,
+        osc2.semitone
+    ),
+    param!(
+        Osc3Semitone,
+        "osc3.semitone",
+        "st",
+        -12.0,
+        12.0,
+        Stepped,
+        Float,
+        0.0,
+        NO_CC,
+        osc3.semitone
+    ),
+    param!(
+        Tuning,
+        "tuning",
+        "",
+        0.0,
+        TUNING_SLOTS as f32,
+        Stepped,
+        Int,
+        0.0,
+        NO_CC,
+        tuning
+    ),
+    param!(
+        NoiseColor,
+        "noise.color",
+        "",
+        0.0,
+        2.0,
+        Stepped,
+        Int,
+        0.0,
+        NO_CC,
+        noise_color
+    ),
+    param!(
+        ExternalLevel,
+        "external.level",
+        "",
+        0.0,
+        1.0,
+        Linear,
+        Float,
+        0.0,
+        NO_CC,
+        external_level
+    ),
+];
+
+impl ParamId {
+    pub fn from_index(index: usize) -> Option<ParamId> {
+        PARAMS.get(index).map(|p| p.id)
+    }
+
+    pub fn from_name(name: &str) -> Option<ParamId> {
+        PARAMS.iter().find(|p| p.name == name).map(|p| p.id)
+    }
+
+    pub fn def(self) -> &'static ParamDef {
+        &PARAMS[self as usize]
+    }
+}
+
+impl ParamDef {
+    pub fn clamp(&self, value: f32) -> f32 {
+        let value = value.clamp(self.min, self.max);
+        match self.curve {
+            Curve::Stepped => libm::roundf(value),
+            _ => value,
+        }
+    }
+
+    pub fn normalize(&self, value: f32) -> f32 {
+        let value = value.clamp(self.min, self.max);
+        let norm = match self.curve {
+            Curve::Linear | Curve::Stepped => (value - self.min) / (self.max - self.min),
+            Curve::Exponential => libm::logf(value / self.min) / libm::logf(self.max / self.min),
+        };
+        norm.clamp(0.0, 1.0)
+    }
+
+    pub fn denormalize(&self, norm: f32) -> f32 {
+        let norm = norm.clamp(0.0, 1.0);
+        match self.curve {
+            Curve::Linear => self.min + norm * (self.max - self.min),
+            Curve::Stepped => libm::roundf(self.min + norm * (self.max - self.min)),
+            Curve::Exponential => self.min * libm::powf(self.max / self.min, norm),
+        }
+    }
+
+    pub fn get(&self, preset: &Preset) -> f32 {
+        let ptr = unsafe { (preset as *const Preset as *const u8).add(self.offset) };
+        match self.kind {
+            ParamKind::Float => unsafe { (ptr as *const f32).read_unaligned() },
+            ParamKind::Int => unsafe { (ptr as *const u32).read_unaligned() as f32 },
+        }
+    }
+
+    pub fn set(&self, preset: &mut Preset, value: f32) {
+        let value = self.clamp(value);
+        let ptr = unsafe { (preset as *mut Preset as *mut u8).add(self.offset) };
+        match self.kind {
+            ParamKind::Float => unsafe { (ptr as *mut f32).write_unaligned(value) },
+            ParamKind::Int => unsafe { (ptr as *mut u32).write_unaligned(value as u32) },
+        }
+    }
+}
+
+pub struct ParamStore {
+    values: [AtomicU32; PARAM_COUNT],
+}
+
+impl ParamStore {
+    pub fn new(preset: &Preset) -> Self {
+        let store = Self {
+            values: core::array::from_fn(|_| AtomicU32::new(0)),
+        };
+        store.load(preset);
+        store
+    }
+
+    pub fn load(&self, preset: &Preset) {
+        for def in PARAMS.iter() {
+            self.set(def.id, def.get(preset));
+        }
+    }
+
+    pub fn copy_from(&self, other: &ParamStore) {
+        for (value, source) in self.values.iter().zip(other.values.iter()) {
+            value.store(source.load(Ordering::Relaxed), Ordering::Relaxed);
+        }
+    }
+
+    pub fn get(&self, id: ParamId) -> f32 {
+        f32::from_bits(self.values[id as usize].load(Ordering::Relaxed))
+    }
+
+    pub fn set(&self, id: ParamId, value: f32) {
+        let value = id.def().clamp(value);
+        self.values[id as usize].store(value.to_bits(), Ordering::Relaxed);
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct CcMap {
+    ccs: [u8; MAX_PARAMS],
+}
+
+impl CcMap {
+    pub fn defaults() -> Self {
+        let mut map = Self {
+            ccs: [NO_CC; MAX_PARAMS],
+        };
+        map.reset_from(0);
+        map
+    }
+
+    pub fn reset_from(&mut self, first: usize) {
+        for def in PARAMS.iter().skip(first) {
+            let cc = def.cc;
+            if self.param_for(cc).is_none() {
+                self.ccs[def.id as usize] = cc;
+            } else {
+                self.ccs[def.id as usize] = NO_CC;
+            }
+        }
+    }
+
+    pub fn param_for(&self, cc: u8) -> Option<ParamId> {
+        self.ccs[..PARAM_COUNT]
+            .iter()
+            .position(|&c| c == cc)
+            .and_then(ParamId::from_index)
+    }
+
+    pub fn cc_for(&self, id: ParamId) -> Option<u8> {
+        match self.ccs[id as usize] {
+            NO_CC => None,
+            cc => Some(cc),
+        }
+    }
+
+    pub fn assign(&mut self, id: ParamId, cc: Option<u8>) {
+        if let Some(cc) = cc {
+            for c in self.ccs.iter_mut().filter(|c| **c == cc) {
+                *c = NO_CC;
+            }
+        }
+        self.ccs[id as usize] = cc.unwrap_or(NO_CC);
+    }
+}
@@ -0,0 +1,141 @@
+use crate::common::shared::MAX_VOICES;
+use crate::control::midi::MidiControl;
+use crate::data::presets::StealMode;
+
+#[derive(Clone, Copy)]
+struct VoiceSlot {
+    note: Option<u8>,
+    sustained: bool,
+    age: u32,
+}
+
+impl VoiceSlot {
+    const fn new() -> Self {
+        Self {
+            note: None,
+            sustained: false,
+            age: 0,
+        }
+    }
+}
+
+pub struct VoiceAllocator {
+    slots: [VoiceSlot; MAX_VOICES],
+    counter: u32,
+    sustain_active: bool,
+}
+
+impl VoiceAllocator {
+    pub fn new() -> Self {
+        Self {
+            slots: [VoiceSlot::new(); MAX_VOICES],
+            counter: 0,
+            sustain_active: false,
+        }
+    }
+
+    fn next_age(&mut self) -> u32 {
+        self.counter = self.counter.wrapping_add(1);
+        self.counter
+    }
+
+    pub fn note_on(
+        &mut self,
+        note: u8,
+        voice_count: usize,
+        steal: StealMode,
+        control: &MidiControl,
+    ) -> (usize, bool) {
+        let voice_count = voice_count.clamp(1, MAX_VOICES);
+        let age = self.next_age();
+
+        if let Some(v) = self.slots[..voice_count]
+            .iter()
+            .position(|s| s.note == Some(note))
+        {
+            self.slots[v].sustained = false;
+            self.slots[v].age = age;
+            return (v, false);
+        }
+
+        let free = self.slots[..voice_count]
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| s.note.is_none())
+            .min_by_key(|(_, s)| s.age)
+            .map(|(v, _)| v);
+
+        let (voice, stolen) = match free {
+            Some(v) => (v, false),
+            None => {
+                let victim = match steal {
+                    StealMode::Oldest => self.slots[..voice_count]
+                        .iter()
+                        .enumerate()
+                        .min_by_key(|(_, s)| s.age)
+                        .map(|(v, _)| v),
+                    StealMode::Quietest => (0..voice_count).min_by(|&a, &b| {
+                        control
+                            .get_voice_level(a)
+                            .total_cmp(&control.get_voice_level(b))
+                    }),
+                };
+                (victim.unwrap_or(0), true)
+            }
+        };
+
+        self.slots[voice] = VoiceSlot {
+            note: Some(note),
+            sustained: false,
+            age,
+        };
+        (voice, stolen)
+    }
+
+    pub fn note_off(&mut self, note: u8) -> Option<usize> {
+        let voice = self.voice_for(note)?;
+        if self.sustain_active {
+            self.slots[voice].sustained = true;
+            None
+        } else {
+            self.release(voice);
+            Some(voice)
+        }
+    }
+
+    pub fn set_sustain(&mut self, active: bool) -> heapless::Vec<usize, MAX_VOICES> {
+        self.sustain_active = active;
+        let mut released = heapless::Vec::new();
+        if !active {
+            for voice in 0..MAX_VOICES {
+                if self.slots[voice].sustained {
+                    self.release(voice);
+                    let _ = released.push(voice);
+                }
+            }
+        }
+        released
+    }
+
+    pub fn voice_for(&self, note: u8) -> Option<usize> {
+        self.slots.iter().position(|s| s.note == Some(note))
+    }
+
+    pub fn any_active(&self) -> bool {
+        self.slots.iter().any(|s| s.note.is_some())
+    }
+
+    pub fn clear(&mut self) {
+        self.slots = [VoiceSlot::new(); MAX_VOICES];
+        self.sustain_active = false;
+    }
+
+    fn release(&mut self, voice: usize) {
+        let age = self.next_age();
+        self.slots[voice] = VoiceSlot {
+            note: None,
+            sustained: false,
+            age,
+        };
+    }
+}
@@ -1,2 +1,4 @@
 pub mod presets;
+pub mod settings;
 pub mod storage;
+pub mod tuning;
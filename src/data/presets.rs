@@ -1,3 +1,4 @@
+use crate::common::shared::MAX_VOICES;
 use infinitedsp_core::synthesis::lfo::LfoWaveform;
 use infinitedsp_core::synthesis::oscillator::Waveform;
 
@@ -10,12 +11,24 @@ pub struct Preset {
     pub osc3: OscSettings,
     pub noise_level: f32,
     pub portamento: f32,
+    pub portamento_mode: u32,
     pub filter: FilterSettings,
     pub amp: EnvelopeSettings,
     pub lfo_enabled: u32,
     pub lfo: LfoSettings,
     pub delay: DelaySettings,
     pub reverb: ReverbSettings,
+    pub voice: VoiceSettings,
+    pub velocity: VelocitySettings,
+    pub aftertouch: AftertouchSettings,
+    pub wheel: WheelSettings,
+    pub bend: BendSettings,
+    pub osc3_mod: Osc3ModSettings,
+    pub pwm: PwmSettings,
+    pub cross_mod: CrossModSettings,
+    pub tuning: u32,
+    pub noise_color: u32,
+    pub external_level: f32,
     pub _padding: [u8; 4],
 }
 
@@ -27,6 +40,9 @@ pub struct OscSettings {
     pub octave: f32,
     pub detune: f32,
     pub enable_vibrato: u32,
+    pub lfo_mode: u32,
+    pub pulse_width: f32,
+    pub semitone: f32,
 }
 
 #[repr(C)]
@@ -39,6 +55,7 @@ pub struct FilterSettings {
     pub decay: f32,
     pub sustain: f32,
     pub release: f32,
+    pub keytrack: u32,
 }
 
 #[repr(C)]
@@ -77,6 +94,107 @@ pub struct ReverbSettings {
     pub enabled: u32,
 }
 
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct VoiceSettings {
+    pub mode: u32,
+    pub count: u32,
+    pub steal: u32,
+    pub priority: u32,
+    pub trigger: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct VelocitySettings {
+    pub amp: f32,
+    pub filter: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct AftertouchSettings {
+    pub vibrato: f32,
+    pub cutoff: f32,
+    pub amp: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct WheelSettings {
+    pub vibrato: f32,
+    pub filter: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct BendSettings {
+    pub up: f32,
+    pub down: f32,
+    pub cutoff: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct Osc3ModSettings {
+    pub pitch: f32,
+    pub cutoff: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct PwmSettings {
+    pub lfo: f32,
+    pub env: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct CrossModSettings {
+    pub sync: u32,
+    pub fm: f32,
+    pub fm_mode: u32,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum NoiseColor {
+    White,
+    Pink,
+    Brown,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum FmMode {
+    Linear,
+    Exponential,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum VoiceMode {
+    Mono,
+    Poly,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum StealMode {
+    Oldest,
+    Quietest,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum NotePriority {
+    Last,
+    Low,
+    High,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum TriggerMode {
+    Legato,
+    Retrigger,
+    LegatoGlide,
+}
+
 impl Preset {
     pub fn get_name(&self) -> &str {
         let len = self
@@ -88,31 +206,89 @@ impl Preset {
     }
 }
 
-impl OscSettings {
-    pub fn get_waveform(&self) -> Waveform {
-        match self.waveform {
-            0 => Waveform::Sine,
-            1 => Waveform::Triangle,
-            2 => Waveform::Saw,
-            3 => Waveform::Square,
-            4 => Waveform::WhiteNoise,
-            _ => Waveform::Saw,
-        }
+pub const WIDE_RECTANGLE: u32 = 5;
+pub const NARROW_RECTANGLE: u32 = 6;
+
+pub fn osc_waveform(value: u32) -> Waveform {
+    match value {
+        0 => Waveform::Sine,
+        1 => Waveform::Triangle,
+        2 => Waveform::Saw,
+        3 | WIDE_RECTANGLE | NARROW_RECTANGLE => Waveform::Square,
+        4 => Waveform::WhiteNoise,
+        _ => Waveform::Saw,
+    }
+}
+
+pub fn rectangle_width(value: u32) -> Option<f32> {
+    match value {
+        WIDE_RECTANGLE => Some(0.3),
+        NARROW_RECTANGLE => Some(0.1),
+        _ => None,
+    }
+}
+
+pub fn fm_mode(value: u32) -> FmMode {
+    match value {
+        1 => FmMode::Exponential,
+        _ => FmMode::Linear,
     }
+}
+
+pub fn lfo_waveform(value: u32) -> LfoWaveform {
+    match value {
+        0 => LfoWaveform::Sine,
+        1 => LfoWaveform::Triangle,
+        2 => LfoWaveform::Saw,
+        3 => LfoWaveform::Square,
+        4 => LfoWaveform::SampleAndHold,
+        _ => LfoWaveform::Sine,
+    }
+}
 
-    pub fn is_vibrato_enabled(&self) -> bool {
-        self.enable_vibrato != 0
+pub fn noise_color(value: u32) -> NoiseColor {
+    match value {
+        1 => NoiseColor::Pink,
+        2 => NoiseColor::Brown,
+        _ => NoiseColor::White,
     }
 }
 
-impl LfoSettings {
-    pub fn get_waveform(&self) -> LfoWaveform {
-        match self.waveform {
-            0 => LfoWaveform::Sine,
-            1 => LfoWaveform::Triangle,
-            2 => LfoWaveform::Saw,
-            3 => LfoWaveform::Square,
-            _ => LfoWaveform::Sine,
+impl VoiceSettings {
+    pub fn get_mode(&self) -> VoiceMode {
+        match self.mode {
+            1 => VoiceMode::Poly,
+            _ => VoiceMode::Mono,
+        }
+    }
+
+    pub fn get_steal_mode(&self) -> StealMode {
+        match self.steal {
+            1 => StealMode::Quietest,
+            _ => StealMode::Oldest,
+        }
+    }
+
+    pub fn get_priority(&self) -> NotePriority {
+        match self.priority {
+            1 => NotePriority::Low,
+            2 => NotePriority::High,
+            _ => NotePriority::Last,
+        }
+    }
+
+    pub fn get_trigger_mode(&self) -> TriggerMode {
+        match self.trigger {
+            1 => TriggerMode::Retrigger,
+            2 => TriggerMode::LegatoGlide,
+            _ => TriggerMode::Legato,
+        }
+    }
+
+    pub fn voice_count(&self) -> usize {
+        match self.get_mode() {
+            VoiceMode::Mono => 1,
+            VoiceMode::Poly => (self.count as usize).clamp(1, MAX_VOICES),
         }
     }
 }
@@ -138,6 +314,9 @@ fn osc(wf: Waveform, level: f32, octave: f32, detune: f32, vib: bool) -> OscSett
         octave,
         detune,
         enable_vibrato: if vib { 1 } else { 0 },
+        lfo_mode: 0,
+        pulse_width: 0.5,
+        semitone: 0.0,
     }
 }
 
@@ -149,7 +328,7 @@ fn lfo(freq: f32, wf: LfoWaveform, vib: f32, filt: f32) -> LfoSettings {
             LfoWaveform::Triangle => 1,
             LfoWaveform::Saw => 2,
             LfoWaveform::Square => 3,
-            _ => 0,
+            LfoWaveform::SampleAndHold => 4,
         },
         vibrato_amount: vib,
         filter_amount: filt,
@@ -165,6 +344,76 @@ fn delay_set(time: f32, feedback: f32, mix: f32, enabled: bool) -> DelaySettings
     }
 }
 
+fn mono(priority: NotePriority, trigger: TriggerMode) -> VoiceSettings {
+    VoiceSettings {
+        mode: 0,
+        count: 1,
+        steal: 0,
+        priority: match priority {
+            NotePriority::Last => 0,
+            NotePriority::Low => 1,
+            NotePriority::High => 2,
+        },
+        trigger: match trigger {
+            TriggerMode::Legato => 0,
+            TriggerMode::Retrigger => 1,
+            TriggerMode::LegatoGlide => 2,
+        },
+    }
+}
+
+fn poly(count: u32, steal: StealMode) -> VoiceSettings {
+    VoiceSettings {
+        mode: 1,
+        count,
+        steal: match steal {
+            StealMode::Oldest => 0,
+            StealMode::Quietest => 1,
+        },
+        priority: 0,
+        trigger: 0,
+    }
+}
+
+fn velocity(amp: f32, filter: f32) -> VelocitySettings {
+    VelocitySettings { amp, filter }
+}
+
+fn aftertouch(vibrato: f32, cutoff: f32, amp: f32) -> AftertouchSettings {
+    AftertouchSettings {
+        vibrato,
+        cutoff,
+        amp,
+    }
+}
+
+fn wheel(vibrato: f32, filter: f32) -> WheelSettings {
+    WheelSettings { vibrato, filter }
+}
+
+fn bend(up: f32, down: f32, cutoff: f32) -> BendSettings {
+    BendSettings { up, down, cutoff }
+}
+
+fn osc3_mod(pitch: f32, cutoff: f32) -> Osc3ModSettings {
+    Osc3ModSettings { pitch, cutoff }
+}
+
+fn pwm(lfo: f32, env: f32) -> PwmSettings {
+    PwmSettings { lfo, env }
+}
+
+fn cross_mod(sync: bool, fm: f32, mode: FmMode) -> CrossModSettings {
+    CrossModSettings {
+        sync: if sync { 1 } else { 0 },
+        fm,
+        fm_mode: match mode {
+            FmMode::Linear => 0,
+            FmMode::Exponential => 1,
+        },
+    }
+}
+
 fn reverb_set(size: f32, damping: f32, mix: f32, enabled: bool) -> ReverbSettings {
     ReverbSettings {
         size,
@@ -183,6 +432,7 @@ impl Default for Preset {
             osc3: osc(Waveform::Saw, 0.0, 0.0, 0.0, true),
             noise_level: 0.0,
             portamento: 0.0,
+            portamento_mode: 0,
             filter: FilterSettings {
                 cutoff: 20000.0,
                 resonance: 0.0,
@@ -191,6 +441,7 @@ impl Default for Preset {
                 decay: 0.0,
                 sustain: 1.0,
                 release: 0.0,
+                keytrack: 0,
             },
             amp: EnvelopeSettings {
                 attack: 0.01,
@@ -202,20 +453,32 @@ impl Default for Preset {
             lfo: lfo(1.0, LfoWaveform::Sine, 0.0, 0.0),
             delay: delay_set(0.25, 0.3, 0.3, false),
             reverb: reverb_set(0.5, 0.5, 0.1, false),
+            voice: mono(NotePriority::Last, TriggerMode::Legato),
+            velocity: velocity(0.0, 0.0),
+            aftertouch: aftertouch(0.0, 0.0, 0.0),
+            wheel: wheel(0.0, 0.0),
+            bend: bend(2.0, 2.0, 0.0),
+            osc3_mod: osc3_mod(0.0, 0.0),
+            pwm: pwm(0.0, 0.0),
+            cross_mod: cross_mod(false, 0.0, FmMode::Linear),
+            tuning: 0,
+            noise_color: 0,
+            external_level: 0.0,
             _padding: [0; 4],
         }
     }
 }
 
-pub fn get_default_presets() -> [Preset; 5] {
+pub fn get_default_presets() -> [Preset; 6] {
     [
         Preset {
             name: make_name("Lucky Man"),
             osc1: osc(Waveform::Square, 1.0, 0.0, 0.0, true),
-            osc2: osc(Waveform::Square, 0.7, 0.0, 2.0, true),
-            osc3: osc(Waveform::Square, 0.7, 0.0, -2.0, true),
+            osc2: osc(Waveform::Square, 0.7, 0.0, 13.0, true),
+            osc3: osc(Waveform::Square, 0.7, 0.0, -13.0, true),
             noise_level: 0.0,
-            portamento: 0.92,
+            portamento: 0.25,
+            portamento_mode: 0,
             filter: FilterSettings {
                 cutoff: 200.0,
                 resonance: 0.4,
@@ -224,6 +487,7 @@ pub fn get_default_presets() -> [Preset; 5] {
                 decay: 1.5,
                 sustain: 0.4,
                 release: 0.5,
+                keytrack: 2,
             },
             amp: EnvelopeSettings {
                 attack: 0.05,
@@ -235,15 +499,27 @@ pub fn get_default_presets() -> [Preset; 5] {
             lfo: lfo(5.0, LfoWaveform::Sine, 2.0, 0.0),
             delay: delay_set(0.4, 0.3, 0.3, true),
             reverb: reverb_set(0.5, 0.5, 0.1, false),
+            voice: mono(NotePriority::Low, TriggerMode::Legato),
+            velocity: velocity(0.0, 0.0),
+            aftertouch: aftertouch(3.0, 0.0, 0.0),
+            wheel: wheel(0.0, 0.0),
+            bend: bend(2.0, 2.0, 0.0),
+            osc3_mod: osc3_mod(0.0, 0.0),
+            pwm: pwm(0.0, 0.0),
+            cross_mod: cross_mod(false, 0.0, FmMode::Linear),
+            tuning: 0,
+            noise_color: 0,
+            external_level: 0.0,
             _padding: [0; 4],
         },
         Preset {
             name: make_name("Tom Sawyer"),
             osc1: osc(Waveform::Saw, 1.0, 0.0, 0.0, false),
-            osc2: osc(Waveform::Saw, 0.5, 0.0, 1.5, false),
+            osc2: osc(Waveform::Saw, 0.5, 0.0, 10.0, false),
             osc3: osc(Waveform::Sine, 0.0, 0.0, 0.0, false),
             noise_level: 0.0,
             portamento: 0.0,
+            portamento_mode: 0,
             filter: FilterSettings {
                 cutoff: 80.0,
                 resonance: 0.45,
@@ -252,6 +528,7 @@ pub fn get_default_presets() -> [Preset; 5] {
                 decay: 2.0,
                 sustain: 0.1,
                 release: 0.1,
+                keytrack: 1,
             },
             amp: EnvelopeSettings {
                 attack: 0.01,
@@ -263,15 +540,27 @@ pub fn get_default_presets() -> [Preset; 5] {
             lfo: lfo(1.0, LfoWaveform::Sine, 0.0, 0.0),
             delay: delay_set(0.15, 0.2, 0.2, true),
             reverb: reverb_set(0.3, 0.5, 0.1, false),
+            voice: mono(NotePriority::Last, TriggerMode::Retrigger),
+            velocity: velocity(0.0, 0.0),
+            aftertouch: aftertouch(0.0, 1500.0, 0.0),
+            wheel: wheel(0.0, 0.0),
+            bend: bend(2.0, 2.0, 0.0),
+            osc3_mod: osc3_mod(0.0, 0.0),
+            pwm: pwm(0.0, 0.0),
+            cross_mod: cross_mod(false, 0.0, FmMode::Linear),
+            tuning: 0,
+            noise_color: 0,
+            external_level: 0.0,
             _padding: [0; 4],
         },
         Preset {
             name: make_name("Moog Scream"),
             osc1: osc(Waveform::Saw, 1.0, 0.0, 0.0, true),
-            osc2: osc(Waveform::Saw, 0.6, 0.0, 2.5, true),
-            osc3: osc(Waveform::Square, 0.8, 0.0, -2.5, true),
+            osc2: osc(Waveform::Saw, 0.6, 0.0, 16.0, true),
+            osc3: osc(Waveform::Square, 0.8, 0.0, -16.0, true),
             noise_level: 0.15,
-            portamento: 0.85,
+            portamento: 0.15,
+            portamento_mode: 0,
             filter: FilterSettings {
                 cutoff: 100.0,
                 resonance: 0.75,
@@ -280,6 +569,7 @@ pub fn get_default_presets() -> [Preset; 5] {
                 decay: 0.3,
                 sustain: 0.2,
                 release: 0.2,
+                keytrack: 2,
             },
             amp: EnvelopeSettings {
                 attack: 0.005,
@@ -291,15 +581,27 @@ pub fn get_default_presets() -> [Preset; 5] {
             lfo: lfo(0.15, LfoWaveform::Sine, 8.0, 0.0),
             delay: delay_set(0.25, 0.3, 0.3, false),
             reverb: reverb_set(0.5, 0.5, 0.2, true),
+            voice: mono(NotePriority::Last, TriggerMode::Legato),
+            velocity: velocity(0.0, 0.0),
+            aftertouch: aftertouch(4.0, 2000.0, 0.0),
+            wheel: wheel(0.0, 0.0),
+            bend: bend(2.0, 2.0, 0.0),
+            osc3_mod: osc3_mod(0.0, 0.0),
+            pwm: pwm(0.0, 0.0),
+            cross_mod: cross_mod(false, 0.0, FmMode::Linear),
+            tuning: 0,
+            noise_color: 0,
+            external_level: 0.0,
             _padding: [0; 4],
         },
         Preset {
             name: make_name("Moog Bass"),
             osc1: osc(Waveform::Saw, 1.0, -3.0, 0.0, false),
-            osc2: osc(Waveform::Saw, 0.4, -3.0, 0.3, false),
+            osc2: osc(Waveform::Saw, 0.4, -3.0, 12.0, false),
             osc3: osc(Waveform::Square, 0.5, -4.0, 0.0, false),
             noise_level: 0.0,
             portamento: 0.0,
+            portamento_mode: 0,
             filter: FilterSettings {
                 cutoff: 80.0,
                 resonance: 0.6,
@@ -308,6 +610,7 @@ pub fn get_default_presets() -> [Preset; 5] {
                 decay: 0.25,
                 sustain: 0.0,
                 release: 0.1,
+                keytrack: 1,
             },
             amp: EnvelopeSettings {
                 attack: 0.001,
@@ -319,15 +622,27 @@ pub fn get_default_presets() -> [Preset; 5] {
             lfo: lfo(1.0, LfoWaveform::Sine, 0.0, 0.0),
             delay: delay_set(0.25, 0.3, 0.3, false),
             reverb: reverb_set(0.5, 0.5, 0.1, false),
+            voice: mono(NotePriority::Low, TriggerMode::Retrigger),
+            velocity: velocity(0.3, 0.5),
+            aftertouch: aftertouch(0.0, 800.0, 0.0),
+            wheel: wheel(0.0, 0.0),
+            bend: bend(2.0, 2.0, 0.0),
+            osc3_mod: osc3_mod(0.0, 0.0),
+            pwm: pwm(0.0, 0.0),
+            cross_mod: cross_mod(false, 0.0, FmMode::Linear),
+            tuning: 0,
+            noise_color: 0,
+            external_level: 0.0,
             _padding: [0; 4],
         },
         Preset {
             name: make_name("Octavarium Lead"),
             osc1: osc(Waveform::Saw, 1.0, 0.0, 0.0, true),
-            osc2: osc(Waveform::Saw, 0.5, 0.0, 2.0, false),
+            osc2: osc(Waveform::Saw, 0.5, 0.0, 13.0, false),
             osc3: osc(Waveform::Square, 0.3, 0.0, 0.0, false),
             noise_level: 0.0,
-            portamento: 0.94,
+            portamento: 0.3,
+            portamento_mode: 0,
             filter: FilterSettings {
                 cutoff: 500.0,
                 resonance: 0.6,
@@ -336,6 +651,7 @@ pub fn get_default_presets() -> [Preset; 5] {
                 decay: 0.5,
                 sustain: 0.6,
                 release: 0.2,
+                keytrack: 2,
             },
             amp: EnvelopeSettings {
                 attack: 0.005,
@@ -347,6 +663,58 @@ pub fn get_default_presets() -> [Preset; 5] {
             lfo: lfo(5.5, LfoWaveform::Sine, 1.5, 0.0),
             delay: delay_set(0.25, 0.3, 0.3, true),
             reverb: reverb_set(0.5, 0.5, 0.1, true),
+            voice: mono(NotePriority::Last, TriggerMode::LegatoGlide),
+            velocity: velocity(0.2, 0.3),
+            aftertouch: aftertouch(3.0, 1000.0, 0.0),
+            wheel: wheel(1.0, 0.0),
+            bend: bend(2.0, 2.0, 0.0),
+            osc3_mod: osc3_mod(0.0, 0.0),
+            pwm: pwm(0.0, 0.0),
+            cross_mod: cross_mod(false, 0.0, FmMode::Linear),
+            tuning: 0,
+            noise_color: 0,
+            external_level: 0.0,
+            _padding: [0; 4],
+        },
+        Preset {
+            name: make_name("Poly Brass"),
+            osc1: osc(Waveform::Saw, 1.0, 0.0, 0.0, true),
+            osc2: osc(Waveform::Saw, 0.8, 0.0, 7.0, true),
+            osc3: osc(Waveform::Saw, 0.0, 0.0, 0.0, false),
+            noise_level: 0.0,
+            portamento: 0.0,
+            portamento_mode: 0,
+            filter: FilterSettings {
+                cutoff: 300.0,
+                resonance: 0.3,
+                env_amount: 2500.0,
+                attack: 0.08,
+                decay: 0.6,
+                sustain: 0.5,
+                release: 0.3,
+                keytrack: 1,
+            },
+            amp: EnvelopeSettings {
+                attack: 0.03,
+                decay: 0.3,
+                sustain: 0.9,
+                release: 0.3,
+            },
+            lfo_enabled: 1,
+            lfo: lfo(4.5, LfoWaveform::Sine, 0.8, 0.0),
+            delay: delay_set(0.3, 0.25, 0.2, false),
+            reverb: reverb_set(0.6, 0.5, 0.15, true),
+            voice: poly(4, StealMode::Oldest),
+            velocity: velocity(0.6, 0.5),
+            aftertouch: aftertouch(1.5, 1200.0, 0.0),
+            wheel: wheel(1.0, 0.0),
+            bend: bend(2.0, 2.0, 0.0),
+            osc3_mod: osc3_mod(0.0, 0.0),
+            pwm: pwm(0.0, 0.0),
+            cross_mod: cross_mod(false, 0.0, FmMode::Linear),
+            tuning: 0,
+            noise_color: 0,
+            external_level: 0.0,
             _padding: [0; 4],
         },
     ]
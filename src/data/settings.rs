@@ -0,0 +1,46 @@
+use crate::control::params::{CcMap, PARAM_COUNT};
+
+pub const OMNI: u8 = 0;
+pub const DEFAULT_MASTER_TUNE: f32 = 440.0;
+pub const MIN_MASTER_TUNE: f32 = 400.0;
+pub const MAX_MASTER_TUNE: f32 = 480.0;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct GlobalSettings {
+    pub param_count: u32,
+    pub cc_map: CcMap,
+    pub midi_channel: u8,
+    pub _padding: [u8; 3],
+    pub master_tune: f32,
+}
+
+impl GlobalSettings {
+    pub fn defaults() -> Self {
+        Self {
+            param_count: PARAM_COUNT as u32,
+            cc_map: CcMap::defaults(),
+            midi_channel: OMNI,
+            _padding: [0; 3],
+            master_tune: DEFAULT_MASTER_TUNE,
+        }
+    }
+
+    pub fn accepts(&self, status: u8) -> bool {
+        self.midi_channel == OMNI || status >= 0xF0 || (status & 0x0F) + 1 == self.midi_channel
+    }
+
+    pub fn upgrade(&mut self) {
+        let known = self.param_count as usize;
+        if known < PARAM_COUNT {
+            self.cc_map.reset_from(known);
+            self.param_count = PARAM_COUNT as u32;
+        }
+        if self.midi_channel > 16 {
+            self.midi_channel = OMNI;
+        }
+        if !(MIN_MASTER_TUNE..=MAX_MASTER_TUNE).contains(&self.master_tune) {
+            self.master_tune = DEFAULT_MASTER_TUNE;
+        }
+    }
+}
@@ -1,4 +1,6 @@
 use crate::data::presets::{get_default_presets, Preset};
+use crate::data::settings::GlobalSettings;
+use crate::data::tuning::{TuningTable, TUNING_SLOTS};
 use crate::usb::logger::SYSTEM_STATUS_CHANNEL;
 use embassy_rp::flash::{Async, Flash, ERASE_SIZE};
 use embassy_rp::peripherals::FLASH;
@@ -6,12 +8,16 @@ use embedded_storage_async::nor_flash::NorFlash;
 
 // "PDSP"
 pub const MAGIC: u32 = 0x50445350;
-pub const VERSION: u32 = 7;
+pub const VERSION: u32 = 23;
+const SETTINGS_VERSION: u32 = 1;
+const TUNING_VERSION: u32 = 1;
 
 const FLASH_SIZE: u32 = 2 * 1024 * 1024;
 const STORAGE_SIZE: u32 = 64 * 1024;
 const ADDR_OFFSET: u32 = FLASH_SIZE - STORAGE_SIZE;
 const SECTOR_SIZE: u32 = 4096;
+const SETTINGS_ADDR: u32 = ADDR_OFFSET + SECTOR_SIZE;
+const TUNING_ADDR: u32 = ADDR_OFFSET + 2 * SECTOR_SIZE;
 
 #[repr(C)]
 struct StorageHeader {
@@ -21,6 +27,14 @@ struct StorageHeader {
     padding: u32,
 }
 
+#[repr(C)]
+struct SettingsHeader {
+    magic: u32,
+    version: u32,
+    size: u32,
+    padding: u32,
+}
+
 pub struct Storage<'d> {
     flash: Flash<'d, FLASH, Async, 2097152>,
 }
@@ -111,8 +125,8 @@ impl<'d> Storage<'d> {
         let preset_size = core::mem::size_of::<Preset>();
         let offset = ADDR_OFFSET + 16 + (index * preset_size) as u32;
 
-        let mut preset_buf = [0u8; 256];
-        if preset_size > 256 {
+        let mut preset_buf = [0u8; 512];
+        if preset_size > preset_buf.len() {
             log_storage!("Error: Preset too large for buffer!\r\n");
             return None;
         }
@@ -128,6 +142,167 @@ impl<'d> Storage<'d> {
         Some(preset)
     }
 
+    pub async fn save_preset(&mut self, index: usize, preset: &Preset) -> bool {
+        let mut sector_buf = [0u8; 4096];
+        self.flash.read(ADDR_OFFSET, &mut sector_buf).await.unwrap();
+        let header: StorageHeader = unsafe { core::ptr::read(sector_buf.as_ptr() as *const _) };
+
+        if index >= header.num_presets as usize {
+            log_storage!("Error: Cannot save preset {}, out of bounds\r\n", index);
+            return false;
+        }
+
+        let size = core::mem::size_of::<Preset>();
+        let start = 16 + index * size;
+        let bytes = unsafe { core::slice::from_raw_parts(preset as *const _ as *const u8, size) };
+        sector_buf[start..start + size].copy_from_slice(bytes);
+
+        self.flash
+            .erase(ADDR_OFFSET, ADDR_OFFSET + ERASE_SIZE as u32)
+            .await
+            .unwrap();
+        self.flash.write(ADDR_OFFSET, &sector_buf).await.unwrap();
+
+        log_storage!("Saved preset {}: {}\r\n", index, preset.get_name());
+        true
+    }
+
+    pub async fn load_settings(&mut self) -> GlobalSettings {
+        let mut buf = [0u8; 512];
+        let size = core::mem::size_of::<GlobalSettings>();
+        self.flash
+            .read(SETTINGS_ADDR, &mut buf[..16 + size])
+            .await
+            .unwrap();
+        let header: SettingsHeader = unsafe { core::ptr::read(buf.as_ptr() as *const _) };
+
+        if header.magic != MAGIC
+            || header.version != SETTINGS_VERSION
+            || header.size as usize > size
+        {
+            log_storage!("Settings missing. Writing defaults...\r\n");
+            let settings = GlobalSettings::defaults();
+            self.save_settings(&settings).await;
+            return settings;
+        }
+
+        let mut settings = GlobalSettings::defaults();
+        let stored = header.size as usize;
+        unsafe {
+            core::ptr::copy_nonoverlapping(
+                buf[16..].as_ptr(),
+                &mut settings as *mut _ as *mut u8,
+                stored,
+            );
+        }
+        settings.upgrade();
+        settings
+    }
+
+    pub async fn save_settings(&mut self, settings: &GlobalSettings) {
+        let mut buf = [0xFFu8; 512];
+        let size = core::mem::size_of::<GlobalSettings>();
+
+        let header = SettingsHeader {
+            magic: MAGIC,
+            version: SETTINGS_VERSION,
+            size: size as u32,
+            padding: 0,
+        };
+        let header_bytes: [u8; 16] = unsafe { core::mem::transmute(header) };
+        buf[0..16].copy_from_slice(&header_bytes);
+
+        let bytes = unsafe { core::slice::from_raw_parts(settings as *const _ as *const u8, size) };
+        buf[16..16 + size].copy_from_slice(bytes);
+
+        self.flash
+            .erase(SETTINGS_ADDR, SETTINGS_ADDR + ERASE_SIZE as u32)
+            .await
+            .unwrap();
+        self.flash.write(SETTINGS_ADDR, &buf).await.unwrap();
+        log_storage!("Saved global settings.\r\n");
+    }
+
+    pub async fn format_tunings(&mut self) {
+        let mut sector_buf = [0xFFu8; 4096];
+        let header = StorageHeader {
+            magic: MAGIC,
+            version: TUNING_VERSION,
+            num_presets: TUNING_SLOTS as u32,
+            padding: 0,
+        };
+        let header_bytes: [u8; 16] = unsafe { core::mem::transmute(header) };
+        sector_buf[0..16].copy_from_slice(&header_bytes);
+
+        let table = TuningTable::equal();
+        let size = core::mem::size_of::<TuningTable>();
+        let bytes = unsafe { core::slice::from_raw_parts(&table as *const _ as *const u8, size) };
+        for slot in 0..TUNING_SLOTS {
+            let start = 16 + slot * size;
+            sector_buf[start..start + size].copy_from_slice(bytes);
+        }
+
+        self.flash
+            .erase(TUNING_ADDR, TUNING_ADDR + ERASE_SIZE as u32)
+            .await
+            .unwrap();
+        self.flash.write(TUNING_ADDR, &sector_buf).await.unwrap();
+    }
+
+    pub async fn load_tuning(&mut self, slot: usize) -> Option<TuningTable> {
+        let mut buf = [0u8; 16];
+        self.flash.read(TUNING_ADDR, &mut buf).await.unwrap();
+        let header: StorageHeader = unsafe { core::ptr::read(buf.as_ptr() as *const _) };
+
+        if header.magic != MAGIC || header.version != TUNING_VERSION {
+            log_storage!("Tuning tables missing. Writing defaults...\r\n");
+            self.format_tunings().await;
+        }
+        if slot >= TUNING_SLOTS {
+            return None;
+        }
+
+        let size = core::mem::size_of::<TuningTable>();
+        let mut table_buf = [0u8; 528];
+        if size > table_buf.len() {
+            log_storage!("Error: Tuning table too large for buffer!\r\n");
+            return None;
+        }
+
+        self.flash
+            .read(
+                TUNING_ADDR + (16 + slot * size) as u32,
+                &mut table_buf[..size],
+            )
+            .await
+            .unwrap();
+        Some(unsafe { core::ptr::read(table_buf.as_ptr() as *const _) })
+    }
+
+    pub async fn save_tuning(&mut self, slot: usize, table: &TuningTable) -> bool {
+        if slot >= TUNING_SLOTS {
+            log_storage!("Error: Cannot save tuning {}, out of bounds\r\n", slot);
+            return false;
+        }
+
+        let mut sector_buf = [0u8; 4096];
+        self.flash.read(TUNING_ADDR, &mut sector_buf).await.unwrap();
+
+        let size = core::mem::size_of::<TuningTable>();
+        let start = 16 + slot * size;
+        let bytes = unsafe { core::slice::from_raw_parts(table as *const _ as *const u8, size) };
+        sector_buf[start..start + size].copy_from_slice(bytes);
+
+        self.flash
+            .erase(TUNING_ADDR, TUNING_ADDR + ERASE_SIZE as u32)
+            .await
+            .unwrap();
+        self.flash.write(TUNING_ADDR, &sector_buf).await.unwrap();
+
+        log_storage!("Saved tuning {}: {}\r\n", slot, table.get_name());
+        true
+    }
+
     pub async fn read_raw(&mut self, buf: &mut [u8]) {
         let len = buf.len().min(SECTOR_SIZE as usize);
         self.flash.read(ADDR_OFFSET, &mut buf[..len]).await.unwrap();
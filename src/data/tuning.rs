@@ -0,0 +1,45 @@
+pub const TUNING_SLOTS: usize = 4;
+pub const NOTE_COUNT: usize = 128;
+
+const MTS_NO_CHANGE: [u8; 3] = [0x7F, 0x7F, 0x7F];
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct TuningTable {
+    pub name: [u8; 16],
+    pub notes: [f32; NOTE_COUNT],
+}
+
+impl TuningTable {
+    pub fn equal() -> Self {
+        let mut name = [0u8; 16];
+        name[..5].copy_from_slice(b"12TET");
+        Self {
+            name,
+            notes: core::array::from_fn(|n| n as f32),
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        let len = self
+            .name
+            .iter()
+            .position(|&c| c == 0)
+            .unwrap_or(self.name.len());
+        core::str::from_utf8(&self.name[..len]).unwrap_or("Invalid")
+    }
+
+    pub fn set_note(&mut self, note: u8, data: &[u8]) {
+        if let (Some(slot), Some(pitch)) = (self.notes.get_mut(note as usize), mts_pitch(data)) {
+            *slot = pitch;
+        }
+    }
+}
+
+fn mts_pitch(data: &[u8]) -> Option<f32> {
+    if data.len() < 3 || data[..3] == MTS_NO_CHANGE {
+        return None;
+    }
+    let fraction = (((data[1] & 0x7F) as u16) << 7) | (data[2] & 0x7F) as u16;
+    Some((data[0] & 0x7F) as f32 + fraction as f32 / 16384.0)
+}
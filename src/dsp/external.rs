@@ -0,0 +1,93 @@
+use heapless::spsc::{Consumer, Producer, Queue};
+use infinitedsp_core::core::channels::Mono;
+use infinitedsp_core::FrameProcessor;
+
+use crate::dsp::signal::SignalBuffer;
+
+pub const INPUT_QUEUE_SIZE: usize = 1024;
+const INPUT_PREFILL: usize = INPUT_QUEUE_SIZE / 2;
+const INPUT_HIGH_WATER: usize = INPUT_QUEUE_SIZE * 3 / 4;
+const UNDERRUN_FADE: f32 = 0.995;
+
+pub type InputQueue = Queue<f32, INPUT_QUEUE_SIZE>;
+
+pub static EXTERNAL_INPUT: SignalBuffer = SignalBuffer::new();
+
+pub struct InputWriter {
+    producer: Producer<'static, f32>,
+}
+
+impl InputWriter {
+    pub fn new(producer: Producer<'static, f32>) -> Self {
+        Self { producer }
+    }
+
+    pub fn push(&mut self, sample: f32) {
+        let _ = self.producer.enqueue(sample);
+    }
+}
+
+pub struct InputReader {
+    consumer: Consumer<'static, f32>,
+    running: bool,
+    last: f32,
+}
+
+impl InputReader {
+    pub fn new(consumer: Consumer<'static, f32>) -> Self {
+        Self {
+            consumer,
+            running: false,
+            last: 0.0,
+        }
+    }
+
+    pub fn read(&mut self, block: &mut [f32]) {
+        if !self.running && self.consumer.len() >= INPUT_PREFILL {
+            self.running = true;
+        }
+        if self.running && self.consumer.len() > INPUT_HIGH_WATER {
+            let _ = self.consumer.dequeue();
+        }
+
+        for sample in block.iter_mut() {
+            let next = if self.running {
+                self.consumer.dequeue()
+            } else {
+                None
+            };
+            match next {
+                Some(value) => self.last = value,
+                None => {
+                    self.running = false;
+                    self.last *= UNDERRUN_FADE;
+                }
+            }
+            *sample = self.last;
+        }
+    }
+}
+
+pub struct ExternalInput;
+
+impl FrameProcessor<Mono> for ExternalInput {
+    fn process(&mut self, buffer: &mut [f32], frame_index: u64) {
+        for (i, sample) in buffer.iter_mut().enumerate() {
+            *sample = EXTERNAL_INPUT.get(frame_index + i as u64);
+        }
+    }
+
+    fn set_sample_rate(&mut self, _sample_rate: f32) {}
+
+    fn reset(&mut self) {}
+
+    fn latency_samples(&self) -> u32 {
+        0
+    }
+    fn name(&self) -> &str {
+        "ExternalInput"
+    }
+    fn visualize(&self, _indent: usize) -> alloc::string::String {
+        "ExternalInput".into()
+    }
+}
@@ -1 +1,5 @@
+pub mod external;
 pub mod moog;
+pub mod oscillator;
+pub mod params;
+pub mod signal;
@@ -5,52 +5,78 @@ use alloc::vec::Vec;
 use infinitedsp_core::core::audio_param::AudioParam;
 use infinitedsp_core::core::channels::Mono;
 use infinitedsp_core::core::dsp_chain::DspChain;
+use infinitedsp_core::core::summing_mixer::SummingMixer;
 use infinitedsp_core::effects::filter::predictive_ladder::PredictiveLadderFilter;
 use infinitedsp_core::effects::utility::gain::Gain;
 use infinitedsp_core::effects::utility::offset::Offset;
 use infinitedsp_core::synthesis::envelope::Adsr;
-use infinitedsp_core::synthesis::lfo::Lfo;
-use infinitedsp_core::synthesis::oscillator::{Oscillator, Waveform};
 use infinitedsp_core::FrameProcessor;
 
 use crate::control::midi::{
     MidiControl, MidiFilterCutoff, MidiFilterResonance, MidiFreq, MidiGate,
 };
-use crate::data::presets::{OscSettings, Preset};
+use crate::control::params::{ParamId, ParamStore};
+use crate::data::presets::{fm_mode, FmMode};
+use crate::dsp::external::ExternalInput;
+use crate::dsp::oscillator::{noise_seed, ModLfo, Noise, Vco};
+use crate::dsp::params::{live, live_mapped, LiveParam};
+use crate::dsp::signal::{SignalBuffer, SignalTap};
+
+const KEYTRACK_CENTER: f32 = 261.63;
+const OSC3_LFO_BASE: f32 = 1.0;
+const FM_LINEAR_RANGE: f32 = 2.0;
+const FM_EXP_RANGE: f32 = 4.0;
+const SILENT_LEVEL: f32 = 0.0001;
+
+const LEVEL_PARAMS: [ParamId; 5] = [
+    ParamId::Osc1Level,
+    ParamId::Osc2Level,
+    ParamId::Osc3Level,
+    ParamId::NoiseLevel,
+    ParamId::ExternalLevel,
+];
+const OSC3_SOURCE: usize = 2;
+const NOISE_SOURCE: usize = 3;
+const EXTERNAL_SOURCE: usize = 4;
+const LFO_SOURCE: usize = 5;
+const RENDER_ORDER: [usize; 5] = [OSC3_SOURCE, 0, 1, NOISE_SOURCE, EXTERNAL_SOURCE];
+const OSC3_MOD_DEPTHS: [ParamId; 3] = [
+    ParamId::Osc3ModPitch,
+    ParamId::Osc3ModCutoff,
+    ParamId::FmAmount,
+];
 
 struct MoogOscillatorSection {
-    osc1: Oscillator,
-    osc2: Oscillator,
-    osc3: Oscillator,
-    noise: Oscillator,
-    level1: f32,
-    level2: f32,
-    level3: f32,
-    level_noise: f32,
+    oscillators: [Vco; 3],
+    noise: Noise,
+    external: ExternalInput,
+    filter_env: Adsr,
+    filter_env_out: Arc<SignalBuffer>,
+    osc3_out: Arc<SignalBuffer>,
+    params: Arc<ParamStore>,
+    levels: [f32; 5],
     scratch_buffer: Vec<f32>,
 }
 
 impl MoogOscillatorSection {
-    #[allow(clippy::too_many_arguments)]
     fn new(
-        osc1: Oscillator,
-        osc2: Oscillator,
-        osc3: Oscillator,
-        noise: Oscillator,
-        l1: f32,
-        l2: f32,
-        l3: f32,
-        ln: f32,
+        oscillators: [Vco; 3],
+        noise: Noise,
+        filter_env: Adsr,
+        filter_env_out: Arc<SignalBuffer>,
+        osc3_out: Arc<SignalBuffer>,
+        params: Arc<ParamStore>,
     ) -> Self {
+        let levels = LEVEL_PARAMS.map(|id| params.get(id));
         Self {
-            osc1,
-            osc2,
-            osc3,
+            oscillators,
             noise,
-            level1: l1,
-            level2: l2,
-            level3: l3,
-            level_noise: ln,
+            external: ExternalInput,
+            filter_env,
+            filter_env_out,
+            osc3_out,
+            params,
+            levels,
             scratch_buffer: vec![0.0; 256],
         }
     }
@@ -63,52 +89,73 @@ impl FrameProcessor<Mono> for MoogOscillatorSection {
             self.scratch_buffer.resize(len, 0.0);
         }
 
-        if self.level1 > 0.0001 {
-            self.osc1.process(buffer, frame_index);
-            for s in buffer.iter_mut() {
-                *s *= self.level1;
+        let env = &mut self.scratch_buffer[0..len];
+        self.filter_env.process(env, frame_index);
+        self.filter_env_out.store(frame_index, Some(env));
+
+        buffer.fill(0.0);
+
+        let targets = LEVEL_PARAMS.map(|id| self.params.get(id));
+        let audible: [bool; 5] =
+            core::array::from_fn(|i| self.levels[i] > SILENT_LEVEL || targets[i] > SILENT_LEVEL);
+        let sync = self.params.get(ParamId::OscSync) != 0.0 && audible[1];
+        let modulating = OSC3_MOD_DEPTHS.iter().any(|&id| self.params.get(id) != 0.0);
+
+        for i in RENDER_ORDER {
+            let start = self.levels[i];
+            let target = targets[i];
+            self.levels[i] = target;
+
+            let master = i == 0 && sync;
+            let modulator = i == OSC3_SOURCE && modulating;
+            if !audible[i] && !master && !modulator {
+                if i == OSC3_SOURCE {
+                    self.osc3_out.store(frame_index, None);
+                }
+                continue;
             }
-        } else {
-            buffer.fill(0.0);
-        }
 
-        if self.level2 > 0.0001 {
-            self.osc2
-                .process(&mut self.scratch_buffer[0..len], frame_index);
-            for (s, scratch) in buffer.iter_mut().zip(self.scratch_buffer.iter()) {
-                *s += *scratch * self.level2;
+            let scratch = &mut self.scratch_buffer[0..len];
+            if i < self.oscillators.len() {
+                let (masters, rest) = self.oscillators.split_at_mut(i);
+                let events = (i == 1 && sync).then(|| masters[0].sync_events());
+                rest[0].process_synced(scratch, frame_index, events);
+                if i == OSC3_SOURCE {
+                    self.osc3_out.store(frame_index, Some(scratch));
+                }
+            } else if i == NOISE_SOURCE {
+                self.noise.process(scratch, frame_index);
+            } else {
+                self.external.process(scratch, frame_index);
             }
-        }
 
-        if self.level3 > 0.0001 {
-            self.osc3
-                .process(&mut self.scratch_buffer[0..len], frame_index);
-            for (s, scratch) in buffer.iter_mut().zip(self.scratch_buffer.iter()) {
-                *s += *scratch * self.level3;
+            if !audible[i] {
+                continue;
             }
-        }
 
-        if self.level_noise > 0.0001 {
-            self.noise
-                .process(&mut self.scratch_buffer[0..len], frame_index);
-            for (s, scratch) in buffer.iter_mut().zip(self.scratch_buffer.iter()) {
-                *s += *scratch * self.level_noise;
+            let step = (target - start) / len as f32;
+            let mut level = start;
+            for (s, v) in buffer.iter_mut().zip(scratch.iter()) {
+                level += step;
+                *s += *v * level;
             }
         }
     }
 
     fn set_sample_rate(&mut self, sample_rate: f32) {
-        self.osc1.set_sample_rate(sample_rate);
-        self.osc2.set_sample_rate(sample_rate);
-        self.osc3.set_sample_rate(sample_rate);
+        for osc in self.oscillators.iter_mut() {
+            osc.set_sample_rate(sample_rate);
+        }
         self.noise.set_sample_rate(sample_rate);
+        self.filter_env.set_sample_rate(sample_rate);
     }
 
     fn reset(&mut self) {
-        self.osc1.reset();
-        self.osc2.reset();
-        self.osc3.reset();
+        for osc in self.oscillators.iter_mut() {
+            osc.reset();
+        }
         self.noise.reset();
+        self.filter_env.reset();
     }
 
     fn latency_samples(&self) -> u32 {
@@ -122,127 +169,498 @@ impl FrameProcessor<Mono> for MoogOscillatorSection {
     }
 }
 
-pub fn new_moog_voice(
-    sample_rate: f32,
-    midi: Arc<MidiControl>,
-    preset: Preset,
-) -> impl FrameProcessor<Mono> + Send {
-    let cutoff_norm = libm::log10f(preset.filter.cutoff / 20.0) / libm::log10f(1000.0);
-    midi.set_parameter_1(cutoff_norm.clamp(0.0, 1.0));
+struct FmRatio {
+    modulator: AudioParam,
+    params: Arc<ParamStore>,
+}
 
-    let res_norm = (preset.filter.resonance - 0.707) / 9.3;
-    midi.set_parameter_2(res_norm.clamp(0.0, 1.0));
+impl FrameProcessor<Mono> for FmRatio {
+    fn process(&mut self, buffer: &mut [f32], frame_index: u64) {
+        self.modulator.process(buffer, frame_index);
+        match fm_mode(self.params.get(ParamId::FmMode) as u32) {
+            FmMode::Linear => {
+                for s in buffer.iter_mut() {
+                    *s = 1.0 + *s * FM_LINEAR_RANGE;
+                }
+            }
+            FmMode::Exponential => {
+                for s in buffer.iter_mut() {
+                    *s = libm::exp2f(*s * FM_EXP_RANGE);
+                }
+            }
+        }
+    }
 
-    let (vibrato_node, filter_lfo_node) = if preset.lfo_enabled != 0 {
-        let p = &preset.lfo;
-        let mut lfo_vib = Lfo::new(AudioParam::Static(p.frequency), p.get_waveform());
-        lfo_vib.set_range(-p.vibrato_amount, p.vibrato_amount);
-        lfo_vib.set_sample_rate(sample_rate);
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.modulator.set_sample_rate(sample_rate);
+    }
 
-        let mut lfo_filt = Lfo::new(AudioParam::Static(p.frequency), p.get_waveform());
-        lfo_filt.set_range(-p.filter_amount, p.filter_amount);
-        lfo_filt.set_sample_rate(sample_rate);
+    fn reset(&mut self) {
+        self.modulator.reset();
+    }
 
-        (Some(lfo_vib), Some(lfo_filt))
-    } else {
-        (None, None)
-    };
+    fn latency_samples(&self) -> u32 {
+        0
+    }
+    fn name(&self) -> &str {
+        "FmRatio"
+    }
+    fn visualize(&self, _indent: usize) -> alloc::string::String {
+        "FmRatio".into()
+    }
+}
+
+struct KeyboardSwitch {
+    keyboard: MidiFreq,
+    params: Arc<ParamStore>,
+    lfo_mode: Option<ParamId>,
+}
+
+impl FrameProcessor<Mono> for KeyboardSwitch {
+    fn process(&mut self, buffer: &mut [f32], frame_index: u64) {
+        if self.lfo_mode.is_some_and(|id| self.params.get(id) != 0.0) {
+            buffer.fill(OSC3_LFO_BASE);
+        } else {
+            self.keyboard.process(buffer, frame_index);
+        }
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.keyboard.set_sample_rate(sample_rate);
+    }
+
+    fn reset(&mut self) {
+        self.keyboard.reset();
+    }
+
+    fn latency_samples(&self) -> u32 {
+        0
+    }
+    fn name(&self) -> &str {
+        "KeyboardSwitch"
+    }
+    fn visualize(&self, _indent: usize) -> alloc::string::String {
+        "KeyboardSwitch".into()
+    }
+}
 
-    let create_pitch = |params: &OscSettings, vib: Option<Lfo>| -> AudioParam {
-        let mut chain = DspChain::new(MidiFreq::new(midi.clone()), sample_rate);
+struct OscMod {
+    osc: SignalTap,
+    params: Arc<ParamStore>,
+    depth: ParamId,
+    current: f32,
+}
 
-        if params.octave != 0.0 {
-            let mult = libm::powf(2.0, params.octave);
-            chain = chain.and(Gain::new_fixed(mult));
+impl FrameProcessor<Mono> for OscMod {
+    fn process(&mut self, buffer: &mut [f32], frame_index: u64) {
+        let target = self.params.get(self.depth);
+        if target == 0.0 && self.current == 0.0 {
+            buffer.fill(0.0);
+            return;
         }
 
-        if params.detune != 0.0 {
-            chain = chain.and(Offset::new(params.detune));
+        self.osc.process(buffer, frame_index);
+        let step = (target - self.current) / buffer.len().max(1) as f32;
+        for s in buffer.iter_mut() {
+            self.current += step;
+            *s *= self.current;
         }
+        self.current = target;
+    }
 
-        if params.is_vibrato_enabled() {
-            if let Some(v) = vib {
-                chain = chain.and(Offset::new_param(AudioParam::Dynamic(Box::new(v))));
-            }
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.osc.set_sample_rate(sample_rate);
+    }
+
+    fn reset(&mut self) {
+        self.osc.reset();
+    }
+
+    fn latency_samples(&self) -> u32 {
+        0
+    }
+    fn name(&self) -> &str {
+        "OscMod"
+    }
+    fn visualize(&self, _indent: usize) -> alloc::string::String {
+        "OscMod".into()
+    }
+}
+
+struct VoiceLevel {
+    env: Adsr,
+    control: Arc<MidiControl>,
+    voice: usize,
+    generation: u32,
+}
+
+impl VoiceLevel {
+    fn publish(&self, level: f32) {
+        if self.control.get_generation() == self.generation {
+            self.control.set_voice_level(self.voice, level);
+        }
+    }
+}
+
+impl FrameProcessor<Mono> for VoiceLevel {
+    fn process(&mut self, buffer: &mut [f32], frame_index: u64) {
+        self.env.process(buffer, frame_index);
+        if let Some(&level) = buffer.last() {
+            self.publish(level);
+        }
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.env.set_sample_rate(sample_rate);
+    }
+
+    fn reset(&mut self) {
+        self.env.reset();
+        self.publish(0.0);
+    }
+
+    fn latency_samples(&self) -> u32 {
+        0
+    }
+    fn name(&self) -> &str {
+        "VoiceLevel"
+    }
+    fn visualize(&self, _indent: usize) -> alloc::string::String {
+        "VoiceLevel".into()
+    }
+}
+
+struct VoiceMod {
+    control: Arc<MidiControl>,
+    params: Arc<ParamStore>,
+    voice: usize,
+    depth: ParamId,
+    source: fn(&MidiControl, usize) -> f32,
+    apply: fn(f32, f32) -> f32,
+    current: f32,
+}
+
+impl VoiceMod {
+    fn new(
+        control: Arc<MidiControl>,
+        params: Arc<ParamStore>,
+        voice: usize,
+        depth: ParamId,
+        source: fn(&MidiControl, usize) -> f32,
+        apply: fn(f32, f32) -> f32,
+    ) -> Self {
+        let mut m = Self {
+            control,
+            params,
+            voice,
+            depth,
+            source,
+            apply,
+            current: 0.0,
+        };
+        m.current = m.target();
+        m
+    }
+
+    fn target(&self) -> f32 {
+        let value = (self.source)(&self.control, self.voice);
+        (self.apply)(value, self.params.get(self.depth))
+    }
+}
+
+impl FrameProcessor<Mono> for VoiceMod {
+    fn process(&mut self, buffer: &mut [f32], _frame_index: u64) {
+        let target = self.target();
+        let step = (target - self.current) / buffer.len().max(1) as f32;
+        for s in buffer.iter_mut() {
+            self.current += step;
+            *s = self.current;
+        }
+        self.current = target;
+    }
+
+    fn set_sample_rate(&mut self, _sample_rate: f32) {}
+
+    fn reset(&mut self) {
+        self.current = self.target();
+    }
+
+    fn latency_samples(&self) -> u32 {
+        0
+    }
+    fn name(&self) -> &str {
+        "VoiceMod"
+    }
+    fn visualize(&self, _indent: usize) -> alloc::string::String {
+        "VoiceMod".into()
+    }
+}
+
+fn velocity(control: &MidiControl, voice: usize) -> f32 {
+    control.get_velocity(voice)
+}
+
+fn wheel(control: &MidiControl, _voice: usize) -> f32 {
+    control.get_mod_wheel()
+}
+
+fn bend(control: &MidiControl, _voice: usize) -> f32 {
+    control.get_bend()
+}
+
+fn key(control: &MidiControl, voice: usize) -> f32 {
+    libm::log2f(control.get_target_freq(voice) / KEYTRACK_CENTER)
+}
+
+fn pressure(control: &MidiControl, voice: usize) -> f32 {
+    control.get_pressure(voice)
+}
+
+fn scale(value: f32, depth: f32) -> f32 {
+    1.0 - depth + depth * value
+}
+
+fn offset(value: f32, depth: f32) -> f32 {
+    value * depth
+}
+
+fn track(octaves: f32, position: f32) -> f32 {
+    libm::exp2f(octaves * position / 3.0)
+}
+
+pub struct PolyVoice<P> {
+    voice: P,
+    control: Arc<MidiControl>,
+    index: usize,
+}
+
+impl<P: FrameProcessor<Mono>> PolyVoice<P> {
+    pub fn new(voice: P, control: Arc<MidiControl>, index: usize) -> Self {
+        Self {
+            voice,
+            control,
+            index,
+        }
+    }
+}
+
+impl<P: FrameProcessor<Mono>> FrameProcessor<Mono> for PolyVoice<P> {
+    fn process(&mut self, buffer: &mut [f32], frame_index: u64) {
+        if self.control.is_voice_active(self.index) {
+            self.voice.process(buffer, frame_index);
+        } else {
+            buffer.fill(0.0);
         }
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.voice.set_sample_rate(sample_rate);
+    }
+
+    fn reset(&mut self) {
+        self.voice.reset();
+    }
+
+    fn latency_samples(&self) -> u32 {
+        0
+    }
+    fn name(&self) -> &str {
+        "PolyVoice"
+    }
+    fn visualize(&self, _indent: usize) -> alloc::string::String {
+        "PolyVoice".into()
+    }
+}
+
+struct OscParams {
+    index: usize,
+    waveform: ParamId,
+    octave: ParamId,
+    semitone: ParamId,
+    detune: ParamId,
+    vibrato: ParamId,
+    pulse_width: ParamId,
+    lfo_mode: Option<ParamId>,
+}
+
+const OSC_PARAMS: [OscParams; 3] = [
+    OscParams {
+        index: 0,
+        waveform: ParamId::Osc1Waveform,
+        octave: ParamId::Osc1Octave,
+        semitone: ParamId::Osc1Semitone,
+        detune: ParamId::Osc1Detune,
+        vibrato: ParamId::Osc1Vibrato,
+        pulse_width: ParamId::Osc1PulseWidth,
+        lfo_mode: None,
+    },
+    OscParams {
+        index: 1,
+        waveform: ParamId::Osc2Waveform,
+        octave: ParamId::Osc2Octave,
+        semitone: ParamId::Osc2Semitone,
+        detune: ParamId::Osc2Detune,
+        vibrato: ParamId::Osc2Vibrato,
+        pulse_width: ParamId::Osc2PulseWidth,
+        lfo_mode: None,
+    },
+    OscParams {
+        index: 2,
+        waveform: ParamId::Osc3Waveform,
+        octave: ParamId::Osc3Octave,
+        semitone: ParamId::Osc3Semitone,
+        detune: ParamId::Osc3Detune,
+        vibrato: ParamId::Osc3Vibrato,
+        pulse_width: ParamId::Osc3PulseWidth,
+        lfo_mode: Some(ParamId::Osc3Lfo),
+    },
+];
+
+pub fn new_moog_voice(
+    sample_rate: f32,
+    midi: Arc<MidiControl>,
+    params: Arc<ParamStore>,
+    voice: usize,
+    generation: u32,
+) -> impl FrameProcessor<Mono> + Send {
+    let voice_mod = |depth: ParamId,
+                     source: fn(&MidiControl, usize) -> f32,
+                     apply: fn(f32, f32) -> f32|
+     -> AudioParam {
+        AudioParam::Dynamic(Box::new(VoiceMod::new(
+            midi.clone(),
+            params.clone(),
+            voice,
+            depth,
+            source,
+            apply,
+        )))
+    };
 
-        AudioParam::Dynamic(Box::new(chain))
+    let lfo_seed = noise_seed(voice, LFO_SOURCE);
+
+    let create_pitch = |osc: &OscParams| -> DspChain<Mono> {
+        let vibrato = ModLfo::new(params.clone(), ParamId::LfoVibrato, Some(osc.vibrato))
+            .with_seed(lfo_seed)
+            .with_depth_scale(voice_mod(ParamId::WheelVibrato, wheel, scale))
+            .with_depth_mod(voice_mod(ParamId::AftertouchVibrato, pressure, offset));
+
+        let source = KeyboardSwitch {
+            keyboard: MidiFreq::new(midi.clone(), voice),
+            params: params.clone(),
+            lfo_mode: osc.lfo_mode,
+        };
+
+        DspChain::new(source, sample_rate)
+            .and(Gain::new(live_mapped(&params, osc.octave, |o| {
+                libm::powf(2.0, o)
+            })))
+            .and(Gain::new(live_mapped(&params, osc.semitone, |s| {
+                libm::exp2f(s / 12.0)
+            })))
+            .and(Gain::new(live_mapped(&params, osc.detune, |c| {
+                libm::exp2f(c / 1200.0)
+            })))
+            .and(Offset::new_param(AudioParam::Dynamic(Box::new(vibrato))))
     };
 
-    let osc1_vib = vibrato_node.as_ref().map(|_l| {
-        let p = &preset.lfo;
-        let mut l = Lfo::new(AudioParam::Static(p.frequency), p.get_waveform());
-        l.set_range(-p.vibrato_amount, p.vibrato_amount);
-        l.set_sample_rate(sample_rate);
-        l
-    });
-    let osc2_vib = vibrato_node.as_ref().map(|_l| {
-        let p = &preset.lfo;
-        let mut l = Lfo::new(AudioParam::Static(p.frequency), p.get_waveform());
-        l.set_range(-p.vibrato_amount, p.vibrato_amount);
-        l.set_sample_rate(sample_rate);
-        l
-    });
-    let osc3_vib = vibrato_node.as_ref().map(|_l| {
-        let p = &preset.lfo;
-        let mut l = Lfo::new(AudioParam::Static(p.frequency), p.get_waveform());
-        l.set_range(-p.vibrato_amount, p.vibrato_amount);
-        l.set_sample_rate(sample_rate);
-        l
-    });
-
-    let mut osc1_node = Oscillator::new(
-        create_pitch(&preset.osc1, osc1_vib),
-        preset.osc1.get_waveform(),
-    );
-    osc1_node.set_sample_rate(sample_rate);
+    let filter_env = Arc::new(SignalBuffer::new());
+
+    let create_vco = |osc: &OscParams, pitch: DspChain<Mono>| -> Vco {
+        let pulse_width =
+            DspChain::new(LiveParam::new(params.clone(), osc.pulse_width), sample_rate)
+                .and(Offset::new_param(AudioParam::Dynamic(Box::new(
+                    ModLfo::new(params.clone(), ParamId::PwmLfo, None).with_seed(lfo_seed),
+                ))))
+                .and(Offset::new_param(AudioParam::Dynamic(Box::new(
+                    DspChain::new(SignalTap::new(filter_env.clone()), sample_rate)
+                        .and(Gain::new(live(&params, ParamId::PwmEnv))),
+                ))));
+
+        let mut node = Vco::new(
+            AudioParam::Dynamic(Box::new(pitch)),
+            params.clone(),
+            osc.waveform,
+        )
+        .with_seed(noise_seed(voice, osc.index))
+        .with_pulse_width(AudioParam::Dynamic(Box::new(pulse_width)));
+        node.set_sample_rate(sample_rate);
+        node
+    };
 
-    let mut osc2_node = Oscillator::new(
-        create_pitch(&preset.osc2, osc2_vib),
-        preset.osc2.get_waveform(),
-    );
-    osc2_node.set_sample_rate(sample_rate);
+    let osc3_out = Arc::new(SignalBuffer::new());
 
-    let mut osc3_node = Oscillator::new(
-        create_pitch(&preset.osc3, osc3_vib),
-        preset.osc3.get_waveform(),
-    );
-    osc3_node.set_sample_rate(sample_rate);
+    let osc3_mod = |depth: ParamId| -> AudioParam {
+        AudioParam::Dynamic(Box::new(OscMod {
+            osc: SignalTap::new(osc3_out.clone()),
+            params: params.clone(),
+            depth,
+            current: 0.0,
+        }))
+    };
+
+    let create_osc = |osc: &OscParams| -> Vco {
+        let mut pitch = create_pitch(osc);
+        if osc.lfo_mode.is_none() {
+            pitch = pitch
+                .and(Offset::new_param(osc3_mod(ParamId::Osc3ModPitch)))
+                .and(Gain::new(AudioParam::Dynamic(Box::new(FmRatio {
+                    modulator: osc3_mod(ParamId::FmAmount),
+                    params: params.clone(),
+                }))));
+        }
+        create_vco(osc, pitch)
+    };
 
-    let mut noise_node = Oscillator::new(AudioParam::Static(0.0), Waveform::WhiteNoise);
-    noise_node.set_sample_rate(sample_rate);
+    let noise_node = Noise::new(params.clone(), noise_seed(voice, NOISE_SOURCE));
 
-    let mixer = MoogOscillatorSection::new(
-        osc1_node,
-        osc2_node,
-        osc3_node,
-        noise_node,
-        preset.osc1.level,
-        preset.osc2.level,
-        preset.osc3.level,
-        preset.noise_level,
+    let contour = Adsr::new(
+        AudioParam::Dynamic(Box::new(MidiGate(midi.clone(), voice))),
+        live(&params, ParamId::FilterAttack),
+        live(&params, ParamId::FilterDecay),
+        live(&params, ParamId::FilterSustain),
+        live(&params, ParamId::FilterRelease),
     );
 
-    let filter_env = Adsr::new(
-        AudioParam::Dynamic(Box::new(MidiGate(midi.clone()))),
-        AudioParam::Static(preset.filter.attack),
-        AudioParam::Static(preset.filter.decay),
-        AudioParam::Static(preset.filter.sustain),
-        AudioParam::Static(preset.filter.release),
+    let mixer = MoogOscillatorSection::new(
+        OSC_PARAMS.each_ref().map(create_osc),
+        noise_node,
+        contour,
+        filter_env.clone(),
+        osc3_out.clone(),
+        params.clone(),
     );
 
     let cutoff_ctrl = MidiFilterCutoff(midi.clone());
 
-    let mut cutoff_mod_chain = DspChain::new(cutoff_ctrl, sample_rate).and(Offset::new_param(
-        AudioParam::Dynamic(Box::new(
-            DspChain::new(filter_env, sample_rate).and(Gain::new_fixed(preset.filter.env_amount)),
-        )),
-    ));
-
-    if let Some(lfo) = filter_lfo_node {
-        cutoff_mod_chain =
-            cutoff_mod_chain.and(Offset::new_param(AudioParam::Dynamic(Box::new(lfo))));
-    }
+    let mut filter_lfo = ModLfo::new(params.clone(), ParamId::LfoFilter, None)
+        .with_seed(lfo_seed)
+        .with_depth_scale(voice_mod(ParamId::WheelFilter, wheel, scale));
+    filter_lfo.set_sample_rate(sample_rate);
+
+    let cutoff_mod_chain = DspChain::new(cutoff_ctrl, sample_rate)
+        .and(Offset::new_param(AudioParam::Dynamic(Box::new(
+            DspChain::new(SignalTap::new(filter_env.clone()), sample_rate)
+                .and(Gain::new(live(&params, ParamId::FilterEnvAmount)))
+                .and(Gain::new(voice_mod(
+                    ParamId::VelocityFilter,
+                    velocity,
+                    scale,
+                ))),
+        ))))
+        .and(Offset::new_param(AudioParam::Dynamic(Box::new(filter_lfo))))
+        .and(Offset::new_param(voice_mod(
+            ParamId::AftertouchCutoff,
+            pressure,
+            offset,
+        )))
+        .and(Offset::new_param(voice_mod(
+            ParamId::BendCutoff,
+            bend,
+            offset,
+        )))
+        .and(Offset::new_param(osc3_mod(ParamId::Osc3ModCutoff)))
+        .and(Gain::new(voice_mod(ParamId::FilterKeytrack, key, track)));
 
     let resonance_ctrl = MidiFilterResonance(midi.clone());
 
@@ -252,14 +670,49 @@ pub fn new_moog_voice(
     );
 
     let amp_env = Adsr::new(
-        AudioParam::Dynamic(Box::new(MidiGate(midi.clone()))),
-        AudioParam::Static(preset.amp.attack),
-        AudioParam::Static(preset.amp.decay),
-        AudioParam::Static(preset.amp.sustain),
-        AudioParam::Static(preset.amp.release),
+        AudioParam::Dynamic(Box::new(MidiGate(midi.clone(), voice))),
+        live(&params, ParamId::AmpAttack),
+        live(&params, ParamId::AmpDecay),
+        live(&params, ParamId::AmpSustain),
+        live(&params, ParamId::AmpRelease),
     );
 
-    let vca = Gain::new(AudioParam::Dynamic(Box::new(amp_env)));
+    let amp_level = VoiceLevel {
+        env: amp_env,
+        control: midi.clone(),
+        voice,
+        generation,
+    };
+
+    let vca = Gain::new(AudioParam::Dynamic(Box::new(amp_level)));
+
+    DspChain::new(mixer, sample_rate)
+        .and(filter_node)
+        .and(vca)
+        .and(Gain::new(voice_mod(ParamId::VelocityAmp, velocity, scale)))
+        .and(Gain::new(voice_mod(
+            ParamId::AftertouchAmp,
+            pressure,
+            scale,
+        )))
+}
 
-    DspChain::new(mixer, sample_rate).and(filter_node).and(vca)
+pub fn new_voice_bank(
+    sample_rate: f32,
+    midi: Arc<MidiControl>,
+    params: &Arc<ParamStore>,
+    voice_count: usize,
+    generation: u32,
+) -> Box<dyn FrameProcessor<Mono> + Send> {
+    let voices: Vec<Box<dyn FrameProcessor<Mono> + Send>> = (0..voice_count)
+        .map(|v| {
+            let voice = new_moog_voice(sample_rate, midi.clone(), params.clone(), v, generation);
+            Box::new(PolyVoice::new(voice, midi.clone(), v)) as Box<dyn FrameProcessor<Mono> + Send>
+        })
+        .collect();
+
+    let mut bank = SummingMixer::new(voices)
+        .with_gain(AudioParam::Static(1.0 / libm::sqrtf(voice_count as f32)));
+    bank.set_sample_rate(sample_rate);
+    Box::new(bank)
 }
@@ -0,0 +1,437 @@
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::f32::consts::PI;
+use infinitedsp_core::core::audio_param::AudioParam;
+use infinitedsp_core::core::channels::Mono;
+use infinitedsp_core::synthesis::lfo::LfoWaveform;
+use infinitedsp_core::synthesis::oscillator::Waveform;
+use infinitedsp_core::FrameProcessor;
+
+use crate::control::params::{ParamId, ParamStore};
+use crate::data::presets::{lfo_waveform, noise_color, osc_waveform, rectangle_width, NoiseColor};
+
+fn poly_blep(t: f32, dt: f32) -> f32 {
+    if t < dt {
+        let t = t / dt;
+        t + t - t * t - 1.0
+    } else if t > 1.0 - dt {
+        let t = (t - 1.0) / dt;
+        t * t + t + t + 1.0
+    } else {
+        0.0
+    }
+}
+
+const MIN_PULSE_WIDTH: f32 = 0.02;
+const NO_SYNC: f32 = -1.0;
+const NOISE_SEED: u32 = 12345;
+
+pub fn noise_seed(voice: usize, source: usize) -> u32 {
+    NOISE_SEED ^ ((voice * 8 + source) as u32).wrapping_mul(0x9E3779B9)
+}
+
+fn next_random(rng_state: &mut u32) -> f32 {
+    *rng_state = rng_state.wrapping_mul(1103515245).wrapping_add(12345);
+    let val = (*rng_state >> 16) & 0x7FFF;
+    (val as f32 / 32768.0) * 2.0 - 1.0
+}
+
+pub struct NoiseSource {
+    rng_state: u32,
+    pink: [f32; 3],
+    brown: f32,
+}
+
+impl NoiseSource {
+    pub fn new(seed: u32) -> Self {
+        Self {
+            rng_state: seed,
+            pink: [0.0; 3],
+            brown: 0.0,
+        }
+    }
+
+    pub fn next(&mut self, color: NoiseColor) -> f32 {
+        let white = next_random(&mut self.rng_state);
+        match color {
+            NoiseColor::White => white,
+            NoiseColor::Pink => {
+                self.pink[0] = 0.99765 * self.pink[0] + white * 0.0990460;
+                self.pink[1] = 0.96300 * self.pink[1] + white * 0.2965164;
+                self.pink[2] = 0.57000 * self.pink[2] + white * 1.0526913;
+                (self.pink[0] + self.pink[1] + self.pink[2] + white * 0.1848) * 0.25
+            }
+            NoiseColor::Brown => {
+                self.brown = (self.brown + 0.02 * white) / 1.02;
+                self.brown * 3.5
+            }
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.pink = [0.0; 3];
+        self.brown = 0.0;
+    }
+}
+
+pub struct Noise {
+    source: NoiseSource,
+    params: Arc<ParamStore>,
+}
+
+impl Noise {
+    pub fn new(params: Arc<ParamStore>, seed: u32) -> Self {
+        Self {
+            source: NoiseSource::new(seed),
+            params,
+        }
+    }
+}
+
+impl FrameProcessor<Mono> for Noise {
+    fn process(&mut self, buffer: &mut [f32], _frame_index: u64) {
+        let color = noise_color(self.params.get(ParamId::NoiseColor) as u32);
+        for sample in buffer.iter_mut() {
+            *sample = self.source.next(color);
+        }
+    }
+
+    fn set_sample_rate(&mut self, _sample_rate: f32) {}
+
+    fn reset(&mut self) {
+        self.source.reset();
+    }
+
+    fn latency_samples(&self) -> u32 {
+        0
+    }
+    fn name(&self) -> &str {
+        "Noise"
+    }
+    fn visualize(&self, _indent: usize) -> alloc::string::String {
+        "Noise".into()
+    }
+}
+
+pub struct Vco {
+    frequency: AudioParam,
+    params: Arc<ParamStore>,
+    waveform: ParamId,
+    pulse_width: Option<AudioParam>,
+    phase: f32,
+    sample_rate: f32,
+    freq_buffer: Vec<f32>,
+    width_buffer: Vec<f32>,
+    sync_buffer: Vec<f32>,
+    rng_state: u32,
+}
+
+impl Vco {
+    pub fn new(frequency: AudioParam, params: Arc<ParamStore>, waveform: ParamId) -> Self {
+        Self {
+            frequency,
+            params,
+            waveform,
+            pulse_width: None,
+            phase: 0.0,
+            sample_rate: 44100.0,
+            freq_buffer: Vec::new(),
+            width_buffer: Vec::new(),
+            sync_buffer: Vec::new(),
+            rng_state: NOISE_SEED,
+        }
+    }
+
+    pub fn with_seed(mut self, seed: u32) -> Self {
+        self.rng_state = seed;
+        self
+    }
+
+    pub fn with_pulse_width(mut self, pulse_width: AudioParam) -> Self {
+        self.pulse_width = Some(pulse_width);
+        self
+    }
+
+    pub fn sync_events(&self) -> &[f32] {
+        &self.sync_buffer
+    }
+
+    pub fn process_synced(&mut self, buffer: &mut [f32], frame_index: u64, sync: Option<&[f32]>) {
+        if self.freq_buffer.len() != buffer.len() {
+            self.freq_buffer.resize(buffer.len(), 0.0);
+        }
+        self.frequency.process(&mut self.freq_buffer, frame_index);
+
+        if self.width_buffer.len() != buffer.len() {
+            self.width_buffer.resize(buffer.len(), 0.5);
+        }
+        if self.sync_buffer.len() != buffer.len() {
+            self.sync_buffer.resize(buffer.len(), NO_SYNC);
+        }
+        if let Some(pulse_width) = self.pulse_width.as_mut() {
+            pulse_width.process(&mut self.width_buffer, frame_index);
+        }
+
+        let value = self.params.get(self.waveform) as u32;
+        let waveform = osc_waveform(value);
+        let rectangle = rectangle_width(value);
+        let inv_sr = 1.0 / self.sample_rate;
+
+        for (i, ((sample, &freq), &width)) in buffer
+            .iter_mut()
+            .zip(self.freq_buffer.iter())
+            .zip(self.width_buffer.iter())
+            .enumerate()
+        {
+            let inc = freq * inv_sr;
+            self.phase += inc;
+            self.sync_buffer[i] = NO_SYNC;
+            if let Some(&master) = sync.and_then(|s| s.get(i)) {
+                if master >= 0.0 {
+                    self.phase = master * inc;
+                }
+            }
+            if self.phase >= 1.0 {
+                self.phase -= 1.0;
+                if inc > 0.0 {
+                    self.sync_buffer[i] = self.phase / inc;
+                }
+            } else if self.phase < 0.0 {
+                self.phase += 1.0;
+            }
+            let phase = self.phase;
+            let dt = inc.abs();
+
+            *sample = match waveform {
+                Waveform::Sine => libm::sinf(phase * 2.0 * PI),
+                Waveform::Triangle => {
+                    if phase < 0.5 {
+                        4.0 * phase - 1.0
+                    } else {
+                        4.0 * (1.0 - phase) - 1.0
+                    }
+                }
+                Waveform::Saw => 2.0 * phase - 1.0 - poly_blep(phase, dt),
+                Waveform::Square => {
+                    let width = rectangle
+                        .unwrap_or(width)
+                        .clamp(MIN_PULSE_WIDTH, 1.0 - MIN_PULSE_WIDTH);
+                    let naive = if phase < width { 1.0 } else { -1.0 };
+                    let mut shifted = phase + 1.0 - width;
+                    if shifted >= 1.0 {
+                        shifted -= 1.0;
+                    }
+                    naive + poly_blep(phase, dt) - poly_blep(shifted, dt) + 1.0 - 2.0 * width
+                }
+                Waveform::WhiteNoise => next_random(&mut self.rng_state),
+            };
+        }
+    }
+}
+
+impl FrameProcessor<Mono> for Vco {
+    fn process(&mut self, buffer: &mut [f32], frame_index: u64) {
+        self.process_synced(buffer, frame_index, None);
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.frequency.set_sample_rate(sample_rate);
+        if let Some(pulse_width) = self.pulse_width.as_mut() {
+            pulse_width.set_sample_rate(sample_rate);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.phase = 0.0;
+        self.frequency.reset();
+        if let Some(pulse_width) = self.pulse_width.as_mut() {
+            pulse_width.reset();
+        }
+    }
+
+    fn latency_samples(&self) -> u32 {
+        0
+    }
+    fn name(&self) -> &str {
+        "Vco"
+    }
+    fn visualize(&self, _indent: usize) -> alloc::string::String {
+        "Vco".into()
+    }
+}
+
+pub struct ModLfo {
+    params: Arc<ParamStore>,
+    depth: ParamId,
+    gate: Option<ParamId>,
+    depth_scale: Option<AudioParam>,
+    depth_mod: Option<AudioParam>,
+    scale_buffer: Vec<f32>,
+    mod_buffer: Vec<f32>,
+    phase: f32,
+    sample_rate: f32,
+    current_depth: f32,
+    noise: NoiseSource,
+    last_sh_value: f32,
+    sh_triggered: bool,
+}
+
+impl ModLfo {
+    pub fn new(params: Arc<ParamStore>, depth: ParamId, gate: Option<ParamId>) -> Self {
+        let mut lfo = Self {
+            params,
+            depth,
+            gate,
+            depth_scale: None,
+            depth_mod: None,
+            scale_buffer: Vec::new(),
+            mod_buffer: Vec::new(),
+            phase: 0.0,
+            sample_rate: 44100.0,
+            current_depth: 0.0,
+            noise: NoiseSource::new(NOISE_SEED),
+            last_sh_value: 0.0,
+            sh_triggered: false,
+        };
+        lfo.current_depth = lfo.target_depth();
+        lfo
+    }
+
+    pub fn with_seed(mut self, seed: u32) -> Self {
+        self.noise = NoiseSource::new(seed);
+        self
+    }
+
+    pub fn with_depth_scale(mut self, depth_scale: AudioParam) -> Self {
+        self.depth_scale = Some(depth_scale);
+        self
+    }
+
+    pub fn with_depth_mod(mut self, depth_mod: AudioParam) -> Self {
+        self.depth_mod = Some(depth_mod);
+        self
+    }
+
+    fn gated(&self) -> bool {
+        self.gate.is_none_or(|g| self.params.get(g) != 0.0)
+    }
+
+    fn target_depth(&self) -> f32 {
+        let enabled = self.params.get(ParamId::LfoEnabled) != 0.0 && self.gated();
+        if enabled {
+            self.params.get(self.depth)
+        } else {
+            0.0
+        }
+    }
+}
+
+impl FrameProcessor<Mono> for ModLfo {
+    fn process(&mut self, buffer: &mut [f32], frame_index: u64) {
+        let target = self.target_depth();
+        let inc = self.params.get(ParamId::LfoRate) / self.sample_rate;
+
+        let gated = self.gated();
+        let mut scaled = false;
+        if let Some(depth_scale) = self.depth_scale.as_mut() {
+            if self.scale_buffer.len() != buffer.len() {
+                self.scale_buffer.resize(buffer.len(), 1.0);
+            }
+            depth_scale.process(&mut self.scale_buffer, frame_index);
+            scaled = true;
+        }
+
+        let mut modulated = false;
+        if let Some(depth_mod) = self.depth_mod.as_mut() {
+            if self.mod_buffer.len() != buffer.len() {
+                self.mod_buffer.resize(buffer.len(), 0.0);
+            }
+            depth_mod.process(&mut self.mod_buffer, frame_index);
+            modulated = gated && self.mod_buffer.iter().any(|&m| m != 0.0);
+        }
+
+        if target == 0.0 && self.current_depth == 0.0 && !modulated {
+            self.phase += inc * buffer.len() as f32;
+            if self.phase >= 1.0 {
+                self.phase -= libm::floorf(self.phase);
+                self.sh_triggered = false;
+            }
+            buffer.fill(0.0);
+            return;
+        }
+
+        let waveform = lfo_waveform(self.params.get(ParamId::LfoWaveform) as u32);
+        let color = noise_color(self.params.get(ParamId::NoiseColor) as u32);
+        let step = (target - self.current_depth) / buffer.len().max(1) as f32;
+
+        for (i, sample) in buffer.iter_mut().enumerate() {
+            let phase = self.phase;
+            self.phase += inc;
+            if self.phase >= 1.0 {
+                self.phase -= 1.0;
+                self.sh_triggered = false;
+            }
+
+            let raw = match waveform {
+                LfoWaveform::Sine => {
+                    let t = 2.0 * libm::fabsf(phase * 2.0 - 1.0) - 1.0;
+                    t * (1.5 - 0.5 * t * t)
+                }
+                LfoWaveform::Triangle => 2.0 * libm::fabsf(phase * 2.0 - 1.0) - 1.0,
+                LfoWaveform::Saw => 2.0 * phase - 1.0,
+                LfoWaveform::Square => {
+                    if phase < 0.5 {
+                        1.0
+                    } else {
+                        -1.0
+                    }
+                }
+                LfoWaveform::SampleAndHold => {
+                    if !self.sh_triggered {
+                        self.last_sh_value = self.noise.next(color);
+                        self.sh_triggered = true;
+                    }
+                    self.last_sh_value
+                }
+            };
+
+            self.current_depth += step;
+            let scale = if scaled { self.scale_buffer[i] } else { 1.0 };
+            let extra = if modulated { self.mod_buffer[i] } else { 0.0 };
+            *sample = raw * (self.current_depth * scale + extra);
+        }
+        self.current_depth = target;
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        if let Some(depth_scale) = self.depth_scale.as_mut() {
+            depth_scale.set_sample_rate(sample_rate);
+        }
+        if let Some(depth_mod) = self.depth_mod.as_mut() {
+            depth_mod.set_sample_rate(sample_rate);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.phase = 0.0;
+        self.sh_triggered = false;
+        if let Some(depth_scale) = self.depth_scale.as_mut() {
+            depth_scale.reset();
+        }
+        if let Some(depth_mod) = self.depth_mod.as_mut() {
+            depth_mod.reset();
+        }
+    }
+
+    fn latency_samples(&self) -> u32 {
+        0
+    }
+    fn name(&self) -> &str {
+        "ModLfo"
+    }
+    fn visualize(&self, _indent: usize) -> alloc::string::String {
+        "ModLfo".into()
+    }
+}
@@ -0,0 +1,158 @@
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use infinitedsp_core::core::audio_param::AudioParam;
+use infinitedsp_core::core::channels::{Mono, Stereo};
+use infinitedsp_core::FrameProcessor;
+
+use crate::control::params::{ParamId, ParamStore};
+
+pub struct LiveParam {
+    params: Arc<ParamStore>,
+    id: ParamId,
+    map: fn(f32) -> f32,
+    current: f32,
+}
+
+impl LiveParam {
+    pub fn new(params: Arc<ParamStore>, id: ParamId) -> Self {
+        Self::mapped(params, id, |v| v)
+    }
+
+    pub fn mapped(params: Arc<ParamStore>, id: ParamId, map: fn(f32) -> f32) -> Self {
+        let current = map(params.get(id));
+        Self {
+            params,
+            id,
+            map,
+            current,
+        }
+    }
+
+    fn target(&self) -> f32 {
+        (self.map)(self.params.get(self.id))
+    }
+}
+
+impl FrameProcessor<Mono> for LiveParam {
+    fn process(&mut self, buffer: &mut [f32], _frame_index: u64) {
+        let target = self.target();
+        if target == self.current || buffer.is_empty() {
+            buffer.fill(target);
+        } else {
+            let step = (target - self.current) / buffer.len() as f32;
+            for s in buffer.iter_mut() {
+                self.current += step;
+                *s = self.current;
+            }
+        }
+        self.current = target;
+    }
+
+    fn set_sample_rate(&mut self, _sample_rate: f32) {}
+
+    fn reset(&mut self) {
+        self.current = self.target();
+    }
+
+    fn latency_samples(&self) -> u32 {
+        0
+    }
+    fn name(&self) -> &str {
+        "LiveParam"
+    }
+    fn visualize(&self, _indent: usize) -> alloc::string::String {
+        "LiveParam".into()
+    }
+}
+
+pub fn live(params: &Arc<ParamStore>, id: ParamId) -> AudioParam {
+    AudioParam::Dynamic(Box::new(LiveParam::new(params.clone(), id)))
+}
+
+pub fn live_mapped(params: &Arc<ParamStore>, id: ParamId, map: fn(f32) -> f32) -> AudioParam {
+    AudioParam::Dynamic(Box::new(LiveParam::mapped(params.clone(), id, map)))
+}
+
+const TAIL_SILENCE: f32 = 0.0001;
+
+pub struct LiveBypass<P> {
+    processor: P,
+    params: Arc<ParamStore>,
+    id: ParamId,
+    enabled: bool,
+    ringing: bool,
+    tail: Vec<f32>,
+}
+
+impl<P: FrameProcessor<Stereo>> LiveBypass<P> {
+    pub fn new(processor: P, params: Arc<ParamStore>, id: ParamId) -> Self {
+        let enabled = params.get(id) != 0.0;
+        Self {
+            processor,
+            params,
+            id,
+            enabled,
+            ringing: false,
+            tail: Vec::new(),
+        }
+    }
+
+    fn ring_out(&mut self, buffer: &mut [f32], frame_index: u64) {
+        if self.tail.len() != buffer.len() {
+            self.tail.resize(buffer.len(), 0.0);
+        }
+        self.tail.fill(0.0);
+        self.processor.process(&mut self.tail, frame_index);
+
+        let mut peak: f32 = 0.0;
+        for (s, t) in buffer.iter_mut().zip(self.tail.iter()) {
+            *s += *t;
+            peak = peak.max(libm::fabsf(*t));
+        }
+
+        if peak < TAIL_SILENCE {
+            self.ringing = false;
+            self.processor.reset();
+        }
+    }
+}
+
+impl<P: FrameProcessor<Stereo>> FrameProcessor<Stereo> for LiveBypass<P> {
+    fn process(&mut self, buffer: &mut [f32], frame_index: u64) {
+        let enabled = self.params.get(self.id) != 0.0;
+        if enabled && !self.enabled && !self.ringing {
+            self.processor.reset();
+        }
+        if !enabled && self.enabled {
+            self.ringing = true;
+        }
+        self.enabled = enabled;
+
+        if enabled {
+            self.ringing = false;
+            self.processor.process(buffer, frame_index);
+        } else if self.ringing {
+            self.ring_out(buffer, frame_index);
+        }
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.processor.set_sample_rate(sample_rate);
+    }
+
+    fn reset(&mut self) {
+        self.ringing = false;
+        self.processor.reset();
+    }
+
+    fn latency_samples(&self) -> u32 {
+        self.processor.latency_samples()
+    }
+    fn name(&self) -> &str {
+        "LiveBypass"
+    }
+    fn visualize(&self, _indent: usize) -> alloc::string::String {
+        "LiveBypass".into()
+    }
+}
@@ -0,0 +1,69 @@
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicU32, Ordering};
+use infinitedsp_core::core::channels::Mono;
+use infinitedsp_core::FrameProcessor;
+
+use crate::common::shared::BLOCK_SIZE;
+
+const FRAMES: usize = BLOCK_SIZE / 2;
+
+pub struct SignalBuffer {
+    start: AtomicU32,
+    samples: [AtomicU32; FRAMES],
+}
+
+impl SignalBuffer {
+    pub const fn new() -> Self {
+        Self {
+            start: AtomicU32::new(0),
+            samples: [const { AtomicU32::new(0) }; FRAMES],
+        }
+    }
+
+    pub fn store(&self, frame_index: u64, block: Option<&[f32]>) {
+        self.start.store(frame_index as u32, Ordering::Relaxed);
+        for (i, s) in self.samples.iter().enumerate() {
+            let value = block.and_then(|b| b.get(i)).copied().unwrap_or(0.0);
+            s.store(value.to_bits(), Ordering::Relaxed);
+        }
+    }
+
+    pub fn get(&self, frame_index: u64) -> f32 {
+        let offset = (frame_index as u32).wrapping_sub(self.start.load(Ordering::Relaxed));
+        self.samples
+            .get(offset as usize)
+            .map_or(0.0, |s| f32::from_bits(s.load(Ordering::Relaxed)))
+    }
+}
+
+pub struct SignalTap {
+    signal: Arc<SignalBuffer>,
+}
+
+impl SignalTap {
+    pub fn new(signal: Arc<SignalBuffer>) -> Self {
+        Self { signal }
+    }
+}
+
+impl FrameProcessor<Mono> for SignalTap {
+    fn process(&mut self, buffer: &mut [f32], frame_index: u64) {
+        for (i, sample) in buffer.iter_mut().enumerate() {
+            *sample = self.signal.get(frame_index + i as u64);
+        }
+    }
+
+    fn set_sample_rate(&mut self, _sample_rate: f32) {}
+
+    fn reset(&mut self) {}
+
+    fn latency_samples(&self) -> u32 {
+        0
+    }
+    fn name(&self) -> &str {
+        "SignalTap"
+    }
+    fn visualize(&self, _indent: usize) -> alloc::string::String {
+        "SignalTap".into()
+    }
+}
@@ -27,9 +27,11 @@ use embassy_rp::usb::InterruptHandler;
 
 use static_cell::StaticCell;
 
-use crate::common::shared::{disable_denormals, CORE1_STACK_SIZE, HEAP_SIZE};
+use crate::common::shared::{disable_denormals, Patch, CORE1_STACK_SIZE, HEAP_SIZE};
+use crate::control::events::{EventQueue, EventScheduler, EventSender};
 use crate::control::midi::MidiControl;
 use crate::data::storage::Storage;
+use crate::dsp::external::{InputQueue, InputReader, InputWriter};
 use crate::tasks::{core0, core1};
 
 #[global_allocator]
@@ -52,6 +54,9 @@ static mut CORE1_STACK: Stack<CORE1_STACK_SIZE> = Stack::new();
 #[link_section = ".sram5"]
 static EXECUTOR1: StaticCell<embassy_executor::Executor> = StaticCell::new();
 
+static EVENT_QUEUE: StaticCell<EventQueue> = StaticCell::new();
+static INPUT_QUEUE: StaticCell<InputQueue> = StaticCell::new();
+
 #[embassy_executor::main]
 async fn main(spawner: Spawner) {
     disable_denormals();
@@ -70,12 +75,18 @@ async fn main(spawner: Spawner) {
 
     let midi_control = Arc::new(MidiControl::new());
 
-    let cutoff_norm = libm::log10f(preset.filter.cutoff / 20.0) / libm::log10f(1000.0);
-    midi_control.set_parameter_1(cutoff_norm.clamp(0.0, 1.0));
-    let res_norm = (preset.filter.resonance - 0.707) / 9.3;
-    midi_control.set_parameter_2(res_norm.clamp(0.0, 1.0));
+    let patch = Patch::new(preset);
+    midi_control.apply_params(&patch.params);
+    midi_control.set_voice_config(&preset.voice);
 
     let midi_control_core1 = midi_control.clone();
+    let patch_core1 = patch.clone();
+
+    let (event_producer, event_consumer) = EVENT_QUEUE.init(EventQueue::new()).split();
+    let events = EventSender::new(event_producer);
+    let scheduler = EventScheduler::new(event_consumer);
+    let (input_producer, input_consumer) = INPUT_QUEUE.init(InputQueue::new()).split();
+    let input_reader = InputReader::new(input_consumer);
 
     unsafe {
         let stack_ptr = addr_of_mut!(CORE1_STACK) as *mut u8;
@@ -91,11 +102,27 @@ async fn main(spawner: Spawner) {
             let executor = EXECUTOR1.init(embassy_executor::Executor::new());
             executor.run(|spawner| {
                 spawner
-                    .spawn(core1::core1_task(midi_control_core1, preset, stack_ptr_val))
+                    .spawn(core1::core1_task(
+                        midi_control_core1,
+                        scheduler,
+                        patch_core1,
+                        input_reader,
+                        stack_ptr_val,
+                    ))
                     .unwrap();
             });
         },
     );
 
-    core0::main_task(spawner, p.USB, p.PIN_25, midi_control, storage).await;
+    core0::main_task(
+        spawner,
+        p.USB,
+        p.PIN_25,
+        midi_control,
+        storage,
+        events,
+        patch,
+        InputWriter::new(input_producer),
+    )
+    .await;
 }
@@ -1,22 +1,97 @@
 use alloc::sync::Arc;
 use embassy_executor::Spawner;
+use embassy_futures::select::{select, Either};
 use embassy_rp::gpio::{Level, Output};
 use embassy_rp::peripherals::{PIN_25, USB};
 use embassy_rp::usb::Driver;
 use embassy_rp::Peri;
 
-use crate::common::shared::{AUDIO_CHANNEL, BLOCK_SIZE, HEAP_SIZE, SAMPLE_RATE};
+use crate::common::shared::{
+    Patch, VoiceBank, AUDIO_CHANNEL, BLOCK_SIZE, HEAP_SIZE, PRESET_CHANNEL, RETIRED_CHANNEL,
+    SAMPLE_RATE, VOICE_BANK_CHANNEL,
+};
+use crate::control::events::EventSender;
 use crate::control::midi::{midi_task, MidiControl};
 use crate::data::storage::Storage;
+use crate::dsp::external::InputWriter;
+use crate::dsp::moog::new_voice_bank;
 use crate::usb::device;
-use crate::usb::logger;
+use crate::usb::logger::{self, SYSTEM_STATUS_CHANNEL};
+use crate::usb::uac1::{Speaker, FEATURE_UNIT};
+use crate::HEAP;
+
+macro_rules! log_status {
+    ($($arg:tt)*) => {
+        {
+            let mut msg = heapless::String::<64>::new();
+            if core::fmt::write(&mut msg, format_args!($($arg)*)).is_ok() {
+                let _ = SYSTEM_STATUS_CHANNEL.send(msg).await;
+            }
+        }
+    };
+}
+
+#[embassy_executor::task]
+async fn voice_builder_task(midi_control: Arc<MidiControl>) {
+    let mut generation = midi_control.get_generation();
+    loop {
+        match select(PRESET_CHANNEL.receive(), RETIRED_CHANNEL.receive()).await {
+            Either::First(patch) => {
+                generation = generation.wrapping_add(1);
+                let processor = new_voice_bank(
+                    SAMPLE_RATE,
+                    midi_control.clone(),
+                    &patch.params,
+                    patch.preset.voice.voice_count(),
+                    generation,
+                );
+                VOICE_BANK_CHANNEL
+                    .send(VoiceBank {
+                        generation,
+                        processor,
+                        params: patch.params.clone(),
+                    })
+                    .await;
+                log_status!("Core 0: Voice bank {} built\r\n", generation);
+            }
+            Either::Second(bank) => {
+                drop(bank);
+                log_status!("Core 0: Memory free: {} KB\r\n", HEAP.free() / 1024);
+            }
+        }
+    }
+}
+
+#[embassy_executor::task]
+async fn external_input_task(
+    mut speaker: Speaker<'static, Driver<'static, USB>>,
+    mut input: InputWriter,
+) {
+    let mut packet = [0u8; 200];
+
+    loop {
+        speaker.wait_enabled().await;
 
+        while let Ok(n) = speaker.read_packet(&mut packet).await {
+            for frame in packet[..n].chunks_exact(4) {
+                let l_sample = i16::from_le_bytes([frame[0], frame[1]]);
+                let r_sample = i16::from_le_bytes([frame[2], frame[3]]);
+                input.push((l_sample as f32 + r_sample as f32) / 65536.0);
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub async fn main_task(
     spawner: Spawner,
     usb: Peri<'static, USB>,
     pin_25: Peri<'static, PIN_25>,
     midi_control: Arc<MidiControl>,
     storage: Storage<'static>,
+    events: EventSender,
+    patch: Patch,
+    input: InputWriter,
 ) {
     let usb_device = {
         let driver = Driver::new(usb, crate::Irqs);
@@ -35,6 +110,12 @@ pub async fn main_task(
             ))
             .unwrap();
         spawner.spawn(logger::led_task(led)).unwrap();
+        spawner
+            .spawn(voice_builder_task(midi_control.clone()))
+            .unwrap();
+        spawner
+            .spawn(external_input_task(device.speaker, input))
+            .unwrap();
 
         spawner
             .spawn(midi_task(
@@ -42,6 +123,8 @@ pub async fn main_task(
                 device.midi_sender,
                 midi_control,
                 storage,
+                events,
+                patch,
             ))
             .unwrap();
 
@@ -53,6 +136,7 @@ pub async fn main_task(
         loop {
             let mut usb_frames_collected = 0;
             let mut usb_audio_bytes = [0u8; 48 * 2 * 2];
+            let gain = FEATURE_UNIT.gain();
 
             while usb_frames_collected < 48 {
                 if dsp_buffer_idx >= BLOCK_SIZE {
@@ -68,8 +152,8 @@ pub async fn main_task(
                     let frames_to_copy = available_frames.min(needed_frames);
 
                     for _ in 0..frames_to_copy {
-                        let l_sample = dsp_buffer[dsp_buffer_idx];
-                        let r_sample = dsp_buffer[dsp_buffer_idx + 1];
+                        let l_sample = dsp_buffer[dsp_buffer_idx] * gain;
+                        let r_sample = dsp_buffer[dsp_buffer_idx + 1] * gain;
                         dsp_buffer_idx += 2;
 
                         let sample_l = (l_sample.clamp(-1.0, 1.0) * 32767.0) as i16;
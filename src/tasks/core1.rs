@@ -1,6 +1,8 @@
-use crate::data::presets::Preset;
-use alloc::boxed::Box;
 use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::f32::consts::FRAC_PI_2;
+use embassy_sync::channel::TrySendError;
 use embassy_time::Instant;
 use infinitedsp_core::core::audio_param::AudioParam;
 use infinitedsp_core::core::channels::{DualMono, Stereo};
@@ -8,17 +10,20 @@ use infinitedsp_core::core::dsp_chain::DspChain;
 use infinitedsp_core::core::parallel_mixer::ParallelMixer;
 use infinitedsp_core::effects::time::delay::Delay;
 use infinitedsp_core::effects::time::reverb::Reverb;
-use infinitedsp_core::effects::utility::bypass::Bypass;
 use infinitedsp_core::effects::utility::gain::Gain;
 use infinitedsp_core::effects::utility::stereo_widener::StereoWidener;
 use infinitedsp_core::FrameProcessor;
 
 use crate::common::shared::{
-    disable_denormals, AudioData, AUDIO_CHANNEL, BLOCK_SIZE, CORE1_STACK_SIZE, PRESET_CHANNEL,
-    SAMPLE_RATE,
+    disable_denormals, AudioData, Patch, VoiceBank, AUDIO_CHANNEL, BLOCK_SIZE, CORE1_STACK_SIZE,
+    MAX_VOICES, RETIRED_CHANNEL, SAMPLE_RATE, VOICE_BANK_CHANNEL,
 };
+use crate::control::events::EventScheduler;
 use crate::control::midi::MidiControl;
-use crate::dsp::moog::new_moog_voice;
+use crate::control::params::{ParamId, ParamStore};
+use crate::dsp::external::{InputReader, EXTERNAL_INPUT};
+use crate::dsp::moog::new_voice_bank;
+use crate::dsp::params::{live, live_mapped, LiveBypass};
 use crate::usb::logger::{LogData, LOG_CHANNEL, SYSTEM_STATUS_CHANNEL};
 use crate::HEAP;
 
@@ -33,49 +38,162 @@ macro_rules! log_status {
     };
 }
 
-fn build_synth(
-    midi_control: Arc<MidiControl>,
-    preset: Preset,
-) -> impl FrameProcessor<Stereo> + Send {
-    let voice = new_moog_voice(SAMPLE_RATE, midi_control, preset);
+const VOICE_CPU_BUDGET: f32 = 0.85;
+const COST_SMOOTHING: f32 = 0.01;
+const CROSSFADE_FRAMES: usize = (SAMPLE_RATE * 0.02) as usize;
+
+struct VoiceBanks {
+    current: Option<VoiceBank>,
+    fading: Option<VoiceBank>,
+    retired: Option<VoiceBank>,
+    fade_pos: usize,
+    scratch: Vec<f32>,
+}
+
+impl VoiceBanks {
+    fn new(initial: VoiceBank) -> Self {
+        Self {
+            current: Some(initial),
+            fading: None,
+            retired: None,
+            fade_pos: 0,
+            scratch: vec![0.0; BLOCK_SIZE / 2],
+        }
+    }
+
+    fn swap_in(&mut self, bank: VoiceBank) {
+        if let Some(old) = self.fading.take() {
+            self.retire(old);
+        }
+        self.fading = self.current.replace(bank);
+        self.fade_pos = 0;
+    }
+
+    fn retire(&mut self, bank: VoiceBank) {
+        if let Err(TrySendError::Full(bank)) = RETIRED_CHANNEL.try_send(bank) {
+            self.retired = Some(bank);
+        }
+    }
+
+    fn flush_retired(&mut self) {
+        if let Some(bank) = self.retired.take() {
+            self.retire(bank);
+        }
+    }
+
+    fn render(&mut self, buffer: &mut [f32], frame_index: u64) {
+        if let Some(bank) = &mut self.current {
+            bank.processor.process(buffer, frame_index);
+        } else {
+            buffer.fill(0.0);
+        }
+
+        let Some(old) = &mut self.fading else {
+            return;
+        };
+
+        let scratch = &mut self.scratch[..buffer.len()];
+        old.processor.process(scratch, frame_index);
+
+        for (s, o) in buffer.iter_mut().zip(scratch.iter()) {
+            let t = (self.fade_pos as f32 / CROSSFADE_FRAMES as f32).min(1.0) * FRAC_PI_2;
+            *s = *s * libm::sinf(t) + *o * libm::cosf(t);
+            self.fade_pos += 1;
+        }
+
+        if self.fade_pos >= CROSSFADE_FRAMES {
+            if let Some(old) = self.fading.take() {
+                self.retire(old);
+            }
+        }
+    }
+}
+
+struct Effects<P> {
+    chain: P,
+    dry: Vec<f32>,
+    wet_gain: f32,
+    flushing: bool,
+}
 
-    let d = &preset.delay;
-    let time_l = d.time;
-    let time_r = d.time * 1.15;
+impl<P: FrameProcessor<Stereo>> Effects<P> {
+    fn new(chain: P) -> Self {
+        Self {
+            chain,
+            dry: vec![0.0; BLOCK_SIZE],
+            wet_gain: 1.0,
+            flushing: false,
+        }
+    }
+
+    fn flush(&mut self) {
+        self.flushing = true;
+    }
 
+    fn process(&mut self, buffer: &mut [f32], frame_index: u64) {
+        self.dry.copy_from_slice(buffer);
+        self.chain.process(buffer, frame_index);
+
+        if !self.flushing && self.wet_gain >= 1.0 {
+            return;
+        }
+
+        let step = 1.0 / CROSSFADE_FRAMES as f32;
+        for (frame, dry) in buffer.chunks_mut(2).zip(self.dry.chunks(2)) {
+            self.wet_gain = if self.flushing {
+                (self.wet_gain - step).max(0.0)
+            } else {
+                (self.wet_gain + step).min(1.0)
+            };
+            for (s, d) in frame.iter_mut().zip(dry.iter()) {
+                *s = *d + (*s - *d) * self.wet_gain;
+            }
+        }
+
+        if self.flushing && self.wet_gain <= 0.0 {
+            self.chain.reset();
+            self.flushing = false;
+        }
+    }
+}
+
+fn build_effects(params: &Arc<ParamStore>) -> impl FrameProcessor<Stereo> + Send {
     let delay_l = Delay::new(
         0.3,
-        AudioParam::Static(time_l),
-        AudioParam::Static(d.feedback),
-        AudioParam::Static(d.mix),
+        live(params, ParamId::DelayTime),
+        live(params, ParamId::DelayFeedback),
+        live(params, ParamId::DelayMix),
     );
 
     let delay_r = Delay::new(
         0.3,
-        AudioParam::Static(time_r),
-        AudioParam::Static(d.feedback),
-        AudioParam::Static(d.mix),
+        live_mapped(params, ParamId::DelayTime, |t| t * 1.15),
+        live(params, ParamId::DelayFeedback),
+        live(params, ParamId::DelayMix),
     );
 
     let delay_node = ParallelMixer::new(1.0, DualMono::new(delay_l, delay_r));
-    let delay_bypass = Bypass::new(delay_node, d.enabled != 0);
+    let delay_bypass = LiveBypass::new(delay_node, params.clone(), ParamId::DelayEnabled);
 
-    let r = &preset.reverb;
-    let reverb =
-        Reverb::new_with_params(AudioParam::Static(r.size), AudioParam::Static(r.damping), 0);
+    let reverb = Reverb::new_with_params(
+        live(params, ParamId::ReverbSize),
+        live(params, ParamId::ReverbDamping),
+        0,
+    );
 
-    let reverb_node = ParallelMixer::new(r.mix, reverb);
-    let reverb_bypass = Bypass::new(reverb_node, r.enabled != 0);
+    let mut reverb_node = ParallelMixer::new(0.0, reverb);
+    reverb_node.set_mix(live(params, ParamId::ReverbMix));
+    let reverb_bypass = LiveBypass::new(reverb_node, params.clone(), ParamId::ReverbEnabled);
 
-    let widener = StereoWidener::new(AudioParam::Static(1.5));
-    let gain = Gain::new_fixed(0.5);
+    DspChain::new(delay_bypass, SAMPLE_RATE).and(reverb_bypass)
+}
 
-    DspChain::new(voice, SAMPLE_RATE)
-        .to_stereo()
-        .and(delay_bypass)
-        .and(reverb_bypass)
-        .and(widener)
-        .and(gain)
+fn smooth_cost(estimate: f32, cost: f32) -> f32 {
+    if estimate == 0.0 {
+        cost
+    } else {
+        estimate + (cost - estimate) * COST_SMOOTHING
+    }
 }
 
 async fn print_stats(stack_ptr: usize) {
@@ -104,49 +222,107 @@ async fn print_stats(stack_ptr: usize) {
 }
 
 #[embassy_executor::task]
-pub async fn core1_task(midi_control: Arc<MidiControl>, initial_preset: Preset, stack_ptr: usize) {
+pub async fn core1_task(
+    midi_control: Arc<MidiControl>,
+    mut events: EventScheduler,
+    initial_patch: Patch,
+    mut input: InputReader,
+    stack_ptr: usize,
+) {
     disable_denormals();
 
     log_status!("Core 1: Starting...\r\n");
-    midi_control.set_portamento(initial_preset.portamento);
     log_status!("Core 1: Measuring memory pressure.\r\n");
 
-    let mut synth: Option<Box<dyn FrameProcessor<Stereo> + Send>> =
-        Some(Box::new(build_synth(midi_control.clone(), initial_preset)));
+    let mut voices = VoiceBanks::new(VoiceBank {
+        generation: midi_control.get_generation(),
+        processor: new_voice_bank(
+            SAMPLE_RATE,
+            midi_control.clone(),
+            &initial_patch.params,
+            initial_patch.preset.voice.voice_count(),
+            midi_control.get_generation(),
+        ),
+        params: initial_patch.params.clone(),
+    });
+    let mut effects = Effects::new(build_effects(&initial_patch.fx_params));
+    let mut output = DspChain::new(StereoWidener::new(AudioParam::Static(1.5)), SAMPLE_RATE)
+        .and(Gain::new_fixed(0.5));
 
     print_stats(stack_ptr).await;
     log_status!("Core 1: DSP Running (STEREO) with Preset\r\n");
 
     let mut buffer = [0.0; BLOCK_SIZE];
+    let mut voice_buffer = [0.0; BLOCK_SIZE / 2];
+    let mut input_buffer = [0.0; BLOCK_SIZE / 2];
     let mut frame_index: u64 = 0;
 
     let max_duration_us = (BLOCK_SIZE as f32 / 2.0 / SAMPLE_RATE * 1_000_000.0) as u64;
+    let mut voice_cost_us: f32 = 0.0;
+    let mut fixed_cost_us: f32 = 0.0;
 
     loop {
-        if let Ok(new_preset) = PRESET_CHANNEL.try_receive() {
-            log_status!("Core 1: Switching Preset...\r\n");
-            let _ = synth.take();
-            print_stats(stack_ptr).await;
-            midi_control.set_portamento(new_preset.portamento);
-
-            synth = Some(Box::new(build_synth(midi_control.clone(), new_preset)));
-
-            log_status!("Core 1: Preset Switched.\r\n");
-            print_stats(stack_ptr).await;
+        voices.flush_retired();
+        if let Ok(bank) = VOICE_BANK_CHANNEL.try_receive() {
+            midi_control.set_generation(bank.generation);
+            if !midi_control.get_fx_tails() {
+                effects.flush();
+            }
+            midi_control.apply_params(&bank.params);
+            initial_patch.fx_params.copy_from(&bank.params);
+            voices.swap_in(bank);
+            voice_cost_us = 0.0;
+            fixed_cost_us = 0.0;
+            midi_control.set_voice_limit(MAX_VOICES);
         }
 
         let start_time = Instant::now();
 
-        if let Some(s) = &mut synth {
-            s.process(&mut buffer, frame_index);
-        } else {
-            buffer.fill(0.0);
+        input.read(&mut input_buffer);
+        EXTERNAL_INPUT.store(frame_index, Some(&input_buffer));
+
+        let frames = BLOCK_SIZE / 2;
+        let mut pos = 0;
+        let mut render_us = 0;
+        events.begin_block();
+        while let Some((offset, event)) = events.next_due(frames) {
+            if offset > pos {
+                let render_start = Instant::now();
+                voices.render(&mut voice_buffer[pos..offset], frame_index + pos as u64);
+                render_us += render_start.elapsed().as_micros();
+                pos = offset;
+            }
+            midi_control.apply_event(&event);
+        }
+        let render_start = Instant::now();
+        voices.render(&mut voice_buffer[pos..], frame_index + pos as u64);
+        render_us += render_start.elapsed().as_micros();
+
+        for (frame, &sample) in buffer.chunks_mut(2).zip(voice_buffer.iter()) {
+            frame[0] = sample;
+            frame[1] = sample;
         }
+        effects.process(&mut buffer, frame_index);
+        output.process(&mut buffer, frame_index);
 
         let end_time = Instant::now();
+        let duration = (end_time - start_time).as_micros();
+
+        let active_voices = (0..MAX_VOICES)
+            .filter(|&v| midi_control.is_voice_active(v))
+            .count();
+        let fading = voices.fading.is_some();
+        if !fading {
+            let fixed = duration.saturating_sub(render_us) as f32;
+            fixed_cost_us = smooth_cost(fixed_cost_us, fixed);
+        }
+        if active_voices > 0 && !fading {
+            voice_cost_us = smooth_cost(voice_cost_us, render_us as f32 / active_voices as f32);
+            let budget = max_duration_us as f32 * VOICE_CPU_BUDGET - fixed_cost_us;
+            midi_control.set_voice_limit((budget.max(0.0) / voice_cost_us) as usize);
+        }
 
         if frame_index % (SAMPLE_RATE as u64) < (BLOCK_SIZE as u64 / 2) {
-            let duration = (end_time - start_time).as_micros();
             let load = (duration as f32 / max_duration_us as f32) * 100.0;
 
             let _ = LOG_CHANNEL.try_send(LogData {
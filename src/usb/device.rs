@@ -9,7 +9,9 @@ use embassy_usb::class::uac1::SampleWidth;
 use embassy_usb::{Builder, Config};
 
 use crate::common::shared::{SystemCommand, COMMAND_CHANNEL};
-use crate::usb::uac1::{self, Microphone, Uac1MicrophoneClass};
+use crate::control::params::ParamId;
+use crate::data::settings::{MAX_MASTER_TUNE, MIN_MASTER_TUNE};
+use crate::usb::uac1::{self, Microphone, Speaker, Uac1AudioClass};
 
 pub type UsbSender = Sender<'static, Driver<'static, USB>>;
 
@@ -22,6 +24,7 @@ static mut UAC_STATE: MaybeUninit<uac1::State<'static>> = MaybeUninit::uninit();
 pub struct UsbDevice {
     pub sender: UsbSender,
     pub microphone: Microphone<'static, Driver<'static, USB>>,
+    pub speaker: Speaker<'static, Driver<'static, USB>>,
     pub midi_receiver: MidiReceiver<'static, Driver<'static, USB>>,
     pub midi_sender: MidiSender<'static, Driver<'static, USB>>,
 }
@@ -63,7 +66,7 @@ pub fn init(spawner: Spawner, driver: Driver<'static, USB>) -> UsbDevice {
         sample_rate: 48000,
         packet_size: 200,
     };
-    let microphone = Uac1MicrophoneClass::new(&mut builder, uac_state, uac_config);
+    let (microphone, speaker) = Uac1AudioClass::new(&mut builder, uac_state, uac_config);
 
     let cdc_class = CdcAcmClass::new(&mut builder, cdc_state, 64);
 
@@ -81,6 +84,7 @@ pub fn init(spawner: Spawner, driver: Driver<'static, USB>) -> UsbDevice {
     UsbDevice {
         sender,
         microphone,
+        speaker,
         midi_receiver,
         midi_sender,
     }
@@ -91,10 +95,48 @@ async fn usb_task(mut usb: embassy_usb::UsbDevice<'static, Driver<'static, USB>>
     usb.run().await;
 }
 
+fn parse_parameter_command(cmd: &str) -> Option<SystemCommand> {
+    let mut parts = cmd.split_whitespace();
+    let verb = parts.next()?;
+    if verb == "channel" {
+        return match parts.next()? {
+            "omni" => Some(SystemCommand::SetChannel(0)),
+            n => n
+                .parse::<u8>()
+                .ok()
+                .filter(|n| (1..=16).contains(n))
+                .map(SystemCommand::SetChannel),
+        };
+    }
+    if verb == "tune" {
+        return parts
+            .next()?
+            .parse::<f32>()
+            .ok()
+            .filter(|hz| (MIN_MASTER_TUNE..=MAX_MASTER_TUNE).contains(hz))
+            .map(SystemCommand::SetMasterTune);
+    }
+    let id = ParamId::from_name(parts.next()?)?;
+    match verb {
+        "get" => Some(SystemCommand::GetParameter(id)),
+        "learn" => Some(SystemCommand::Learn(Some(id))),
+        "unlearn" => Some(SystemCommand::SetCc(id, None)),
+        "cc" => {
+            let cc = parts.next()?.parse::<u8>().ok().filter(|cc| *cc < 128)?;
+            Some(SystemCommand::SetCc(id, Some(cc)))
+        }
+        "set" => {
+            let value = parts.next()?.parse::<f32>().ok()?;
+            Some(SystemCommand::SetParameter(id, value))
+        }
+        _ => None,
+    }
+}
+
 #[embassy_executor::task]
 async fn command_listener_task(mut receiver: Receiver<'static, Driver<'static, USB>>) {
     let mut buf = [0; 64];
-    let mut line_buf = heapless::String::<32>::new();
+    let mut line_buf = heapless::String::<64>::new();
 
     loop {
         receiver.wait_connection().await;
@@ -117,7 +159,26 @@ async fn command_listener_task(mut receiver: Receiver<'static, Driver<'static, U
                         "reset" => {
                             let _ = COMMAND_CHANNEL.try_send(SystemCommand::ResetStorage);
                         }
-                        _ => {}
+                        "params" => {
+                            let _ = COMMAND_CHANNEL.try_send(SystemCommand::ListParameters);
+                        }
+                        "save" => {
+                            let _ = COMMAND_CHANNEL.try_send(SystemCommand::SavePreset);
+                        }
+                        "tails on" => {
+                            let _ = COMMAND_CHANNEL.try_send(SystemCommand::SetFxTails(true));
+                        }
+                        "tails off" => {
+                            let _ = COMMAND_CHANNEL.try_send(SystemCommand::SetFxTails(false));
+                        }
+                        "learn off" => {
+                            let _ = COMMAND_CHANNEL.try_send(SystemCommand::Learn(None));
+                        }
+                        _ => {
+                            if let Some(command) = parse_parameter_command(cmd) {
+                                let _ = COMMAND_CHANNEL.try_send(command);
+                            }
+                        }
                     }
                     line_buf.clear();
                 } else {
@@ -1,9 +1,48 @@
+use core::sync::atomic::{AtomicBool, AtomicI16, Ordering};
 use embassy_usb::class::uac1::SampleWidth;
-use embassy_usb::control::{InResponse, OutResponse, Request};
+use embassy_usb::control::{InResponse, OutResponse, Recipient, Request, RequestType};
 use embassy_usb::descriptor::{SynchronizationType, UsageType};
-use embassy_usb::driver::{Driver, EndpointError, EndpointIn};
+use embassy_usb::driver::{Driver, Endpoint, EndpointError, EndpointIn, EndpointOut};
 use embassy_usb::{Builder, Handler};
 
+const FEATURE_UNIT_ID: u8 = 0x02;
+const CS_MUTE: u8 = 0x01;
+const CS_VOLUME: u8 = 0x02;
+
+const SET_CUR: u8 = 0x01;
+const GET_CUR: u8 = 0x81;
+const GET_MIN: u8 = 0x82;
+const GET_MAX: u8 = 0x83;
+const GET_RES: u8 = 0x84;
+
+const VOLUME_MIN: i16 = -0x4000;
+const VOLUME_MAX: i16 = 0;
+const VOLUME_RES: i16 = 0x0100;
+
+pub struct FeatureUnit {
+    mute: AtomicBool,
+    volume: AtomicI16,
+}
+
+impl FeatureUnit {
+    const fn new() -> Self {
+        Self {
+            mute: AtomicBool::new(false),
+            volume: AtomicI16::new(VOLUME_MAX),
+        }
+    }
+
+    pub fn gain(&self) -> f32 {
+        if self.mute.load(Ordering::Relaxed) {
+            return 0.0;
+        }
+        let db = self.volume.load(Ordering::Relaxed) as f32 / 256.0;
+        libm::powf(10.0, db / 20.0)
+    }
+}
+
+pub static FEATURE_UNIT: FeatureUnit = FeatureUnit::new();
+
 #[derive(Clone, Copy)]
 pub struct Config {
     pub audio_format: SampleWidth,
@@ -38,54 +77,64 @@ struct Control<'d> {
 }
 
 impl<'d> Handler for Control<'d> {
-    fn control_out(&mut self, _req: Request, _data: &[u8]) -> Option<OutResponse> {
+    fn control_out(&mut self, req: Request, data: &[u8]) -> Option<OutResponse> {
+        if !is_feature_unit(&req) {
+            return None;
+        }
+        if req.request != SET_CUR {
+            return Some(OutResponse::Rejected);
+        }
+
+        let cs = (req.value >> 8) as u8;
+        match (cs, data) {
+            (CS_MUTE, [mute, ..]) => {
+                FEATURE_UNIT.mute.store(*mute != 0, Ordering::Relaxed);
+            }
+            (CS_VOLUME, [lo, hi, ..]) => {
+                let volume = i16::from_le_bytes([*lo, *hi]).clamp(VOLUME_MIN, VOLUME_MAX);
+                FEATURE_UNIT.volume.store(volume, Ordering::Relaxed);
+            }
+            _ => return Some(OutResponse::Rejected),
+        }
         Some(OutResponse::Accepted)
     }
 
     fn control_in<'a>(&'a mut self, req: Request, buf: &'a mut [u8]) -> Option<InResponse<'a>> {
-        if req.request_type == embassy_usb::control::RequestType::Class
-            && req.recipient == embassy_usb::control::Recipient::Interface
-        {
-            let cs = (req.value >> 8) as u8;
-
-            if cs == 0x01 {
-                match req.request {
-                    0x81 => {
-                        buf[0] = 0;
-                        return Some(InResponse::Accepted(&buf[..1]));
-                    }
-                    _ => return None,
-                }
-            } else if cs == 0x02 {
-                match req.request {
-                    0x81 => {
-                        buf[0] = 0x00;
-                        buf[1] = 0x00;
-                        return Some(InResponse::Accepted(&buf[..2]));
-                    }
-                    0x82 => {
-                        buf[0] = 0x00;
-                        buf[1] = 0xC0;
-                        return Some(InResponse::Accepted(&buf[..2]));
-                    }
-                    0x83 => {
-                        buf[0] = 0x00;
-                        buf[1] = 0x00;
-                        return Some(InResponse::Accepted(&buf[..2]));
-                    }
-                    0x84 => {
-                        buf[0] = 0x00;
-                        buf[1] = 0x01;
-                        return Some(InResponse::Accepted(&buf[..2]));
-                    }
-                    _ => return None,
+        if !is_feature_unit(&req) {
+            return None;
+        }
+
+        let cs = (req.value >> 8) as u8;
+        if cs == CS_MUTE {
+            match req.request {
+                GET_CUR => {
+                    buf[0] = FEATURE_UNIT.mute.load(Ordering::Relaxed) as u8;
+                    Some(InResponse::Accepted(&buf[..1]))
                 }
+                _ => None,
             }
+        } else if cs == CS_VOLUME {
+            let value = match req.request {
+                GET_CUR => FEATURE_UNIT.volume.load(Ordering::Relaxed),
+                GET_MIN => VOLUME_MIN,
+                GET_MAX => VOLUME_MAX,
+                GET_RES => VOLUME_RES,
+                _ => return None,
+            };
+            buf[..2].copy_from_slice(&value.to_le_bytes());
+            Some(InResponse::Accepted(&buf[..2]))
+        } else {
+            None
         }
-        None
     }
 }
 
+fn is_feature_unit(req: &Request) -> bool {
+    req.request_type == RequestType::Class
+        && req.recipient == Recipient::Interface
+        && (req.index >> 8) as u8 == FEATURE_UNIT_ID
+}
+
 pub struct Microphone<'d, D: Driver<'d>> {
     ep_in: D::EndpointIn,
 }
@@ -96,15 +145,29 @@ impl<'d, D: Driver<'d>> Microphone<'d, D> {
     }
 }
 
-pub struct Uac1MicrophoneClass;
+pub struct Speaker<'d, D: Driver<'d>> {
+    ep_out: D::EndpointOut,
+}
 
-impl Uac1MicrophoneClass {
+impl<'d, D: Driver<'d>> Speaker<'d, D> {
+    pub async fn wait_enabled(&mut self) {
+        self.ep_out.wait_enabled().await
+    }
+
+    pub async fn read_packet(&mut self, data: &mut [u8]) -> Result<usize, EndpointError> {
+        self.ep_out.read(data).await
+    }
+}
+
+pub struct Uac1AudioClass;
+
+impl Uac1AudioClass {
     #[allow(clippy::new_ret_no_self)]
     pub fn new<'d, D: Driver<'d>>(
         builder: &mut Builder<'d, D>,
         state: &'d mut State<'d>,
         config: Config,
-    ) -> Microphone<'d, D> {
+    ) -> (Microphone<'d, D>, Speaker<'d, D>) {
         state.control = Some(Control {
             _marker: core::marker::PhantomData,
         });
@@ -117,18 +180,19 @@ impl Uac1MicrophoneClass {
         let ac_if_num = ac_if.interface_number();
         let mut alt = ac_if.alt_setting(0x01, 0x01, 0x00, None);
 
-        let total_length: u16 = 40;
+        let total_length: u16 = 62;
 
         let header_desc = [
-            0x09,
+            0x0A,
             0x24,
             0x01,
             0x00,
             0x01,
             (total_length & 0xff) as u8,
             (total_length >> 8) as u8,
-            0x01,
+            0x02,
             (ac_if_num.0 + 1),
+            (ac_if_num.0 + 2),
         ];
         alt.descriptor(0x24, &header_desc[2..]);
 
@@ -154,14 +218,24 @@ impl Uac1MicrophoneClass {
         let ot_desc = [0x09, 0x24, 0x03, 0x03, 0x01, 0x01, 0x00, 0x02, 0x00];
         alt.descriptor(0x24, &ot_desc[2..]);
 
-        let mut as_if = func.interface();
-
-        let _alt0 = as_if.alt_setting(0x01, 0x02, 0x00, None);
-
-        let mut alt1 = as_if.alt_setting(0x01, 0x02, 0x00, None);
+        let spk_it_desc = [
+            0x0C,
+            0x24,
+            0x02,
+            0x04,
+            0x01,
+            0x01,
+            0x00,
+            config.channel_count,
+            0x03,
+            0x00,
+            0x00,
+            0x00,
+        ];
+        alt.descriptor(0x24, &spk_it_desc[2..]);
 
-        let as_general_desc = [0x07, 0x24, 0x01, 0x03, 0x01, 0x01, 0x00];
-        alt1.descriptor(0x24, &as_general_desc[2..]);
+        let spk_ot_desc = [0x09, 0x24, 0x03, 0x05, 0x01, 0x03, 0x00, 0x04, 0x00];
+        alt.descriptor(0x24, &spk_ot_desc[2..]);
 
         let format_desc = [
             0x08 + 3,
@@ -176,9 +250,18 @@ impl Uac1MicrophoneClass {
             ((config.sample_rate >> 8) & 0xff) as u8,
             ((config.sample_rate >> 16) & 0xff) as u8,
         ];
-        alt1.descriptor(0x24, &format_desc[2..]);
 
         let cs_ep_desc = [0x07, 0x25, 0x01, 0x00, 0x00, 0x00, 0x00];
+
+        let mut as_if = func.interface();
+
+        let _alt0 = as_if.alt_setting(0x01, 0x02, 0x00, None);
+
+        let mut alt1 = as_if.alt_setting(0x01, 0x02, 0x00, None);
+
+        let as_general_desc = [0x07, 0x24, 0x01, 0x03, 0x01, 0x01, 0x00];
+        alt1.descriptor(0x24, &as_general_desc[2..]);
+        alt1.descriptor(0x24, &format_desc[2..]);
         alt1.descriptor(0x25, &cs_ep_desc[2..]);
 
         let ep_in = alt1.endpoint_isochronous_in(
@@ -190,6 +273,26 @@ impl Uac1MicrophoneClass {
             &[],
         );
 
-        Microphone { ep_in }
+        let mut spk_if = func.interface();
+
+        let _spk_alt0 = spk_if.alt_setting(0x01, 0x02, 0x00, None);
+
+        let mut spk_alt1 = spk_if.alt_setting(0x01, 0x02, 0x00, None);
+
+        let spk_general_desc = [0x07, 0x24, 0x01, 0x04, 0x01, 0x01, 0x00];
+        spk_alt1.descriptor(0x24, &spk_general_desc[2..]);
+        spk_alt1.descriptor(0x24, &format_desc[2..]);
+        spk_alt1.descriptor(0x25, &cs_ep_desc[2..]);
+
+        let ep_out = spk_alt1.endpoint_isochronous_out(
+            None,
+            config.packet_size,
+            1,
+            SynchronizationType::Adaptive,
+            UsageType::DataEndpoint,
+            &[],
+        );
+
+        (Microphone { ep_in }, Speaker { ep_out })
     }
 }